@@ -0,0 +1,191 @@
+use std::{
+    io,
+    os::unix::process::CommandExt,
+    path::PathBuf,
+    process::{Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::{Datelike, Local, NaiveTime, Weekday};
+use log::{debug, error, info, warn};
+
+use crate::change::switch_project;
+use crate::output::say;
+use wechsel::{
+    ChangeOptions, ChangeResult, Navigation, Rule, WechselError, find_project, last_manual_switch,
+    load_config, now, query_active_project,
+};
+
+// A probe that takes longer than this doesn't match, so a hanging command can't block the daemon
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Returns the project of the first matching rule
+pub fn evaluate_rules(rules: &[Rule]) -> Result<Option<String>, WechselError> {
+    let now = Local::now();
+    for rule in rules {
+        if rule_matches(rule, now.weekday(), now.time())? {
+            return Ok(Some(rule.project.clone()));
+        }
+    }
+    Ok(None)
+}
+
+fn rule_matches(rule: &Rule, weekday: Weekday, time: NaiveTime) -> Result<bool, WechselError> {
+    let days = rule.weekdays().map_err(WechselError::Invalid)?;
+    if !days.is_empty() && !days.contains(&weekday) {
+        return Ok(false);
+    }
+
+    let (from, to) = rule.window().map_err(WechselError::Invalid)?;
+    let in_window = match (from, to) {
+        (Some(from), Some(to)) if from <= to => from <= time && time < to,
+        // The window wraps around midnight
+        (Some(from), Some(to)) => from <= time || time < to,
+        (Some(from), None) => from <= time,
+        (None, Some(to)) => time < to,
+        (None, None) => true,
+    };
+    if !in_window {
+        return Ok(false);
+    }
+
+    Ok(rule
+        .probe
+        .as_ref()
+        .map(|probe| {
+            let start = Instant::now();
+            let status = run_probe(probe);
+            debug!(
+                "Probe {probe:?} of the rule for {} finished after {:?}: {status:?}",
+                rule.project,
+                start.elapsed()
+            );
+            status
+                .ok()
+                .flatten()
+                .map(|status| status.success())
+                .unwrap_or_default()
+        })
+        .unwrap_or(true))
+}
+
+// Runs the probe in a process group of its own, which is killed once it takes longer than
+// PROBE_TIMEOUT. `None` if it timed out.
+fn run_probe(probe: &str) -> io::Result<Option<ExitStatus>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(probe)
+        .stdin(Stdio::null())
+        .process_group(0)
+        .spawn()?;
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if start.elapsed() >= PROBE_TIMEOUT {
+            warn!("The probe {probe:?} timed out after {:?}", start.elapsed());
+            // SAFETY: kill has no memory safety requirements, the group is the one of the probe
+            unsafe { libc::kill(-(child.id() as i32), libc::SIGKILL) };
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn in_grace_period(grace_period: u64) -> bool {
+    last_manual_switch()
+        .map(|time| now() - time < grace_period as i64)
+        .unwrap_or_default()
+}
+
+/// Evaluates the rules once and changes the project if needed.
+/// Returns the change, if one was made.
+pub fn auto_once(config_dir: &PathBuf) -> Result<Option<ChangeResult>, WechselError> {
+    let config = load_config(config_dir)?.auto;
+
+    let Some(target) = evaluate_rules(&config.rules)? else {
+        say!("No rule matches");
+        return Ok(None);
+    };
+    if query_active_project(config_dir).as_deref() == Some(target.as_str()) {
+        say!("Already in Project {target}");
+        return Ok(None);
+    }
+    if in_grace_period(config.grace_period) {
+        say!("The project was changed manually recently, not changing to {target}");
+        return Ok(None);
+    }
+    let prj = match find_project(&target, config_dir) {
+        Err(WechselError::NotFound(_)) => {
+            return Err(WechselError::NotFound(format!(
+                "The project {target} of a rule could not be found"
+            )));
+        }
+        prj => prj?,
+    };
+    switch_project(
+        &prj,
+        config_dir,
+        Navigation::Switch,
+        true,
+        ChangeOptions::default(),
+    )
+    .map(Some)
+}
+
+/// Re-evaluates the rules periodically, the project is only changed when the result of the rules changes.
+/// Errors while changing are printed and don't stop the daemon, neither do errors in a changed config.
+pub fn daemon(config_dir: &PathBuf) -> Result<(), WechselError> {
+    let mut config = load_config(config_dir)?.auto;
+    let mut last_result: Option<String> = None;
+    loop {
+        // Reloaded every time so config changes apply without a restart,
+        // a broken config is reported and the last one that worked stays in use
+        match load_config(config_dir) {
+            Ok(loaded) => config = loaded.auto,
+            Err(e) => error!("{e}"),
+        }
+        let result = match evaluate_rules(&config.rules) {
+            Ok(result) => result,
+            Err(e) => {
+                error!("{e}");
+                last_result.clone()
+            }
+        };
+
+        if result != last_result {
+            info!("The rules now select {result:?}");
+            if let Some(target) = &result {
+                if query_active_project(config_dir).as_deref() != Some(target.as_str())
+                    && !in_grace_period(config.grace_period)
+                {
+                    match find_project(target, config_dir) {
+                        Ok(prj) => {
+                            if let Err(e) = switch_project(
+                                &prj,
+                                config_dir,
+                                Navigation::Switch,
+                                true,
+                                ChangeOptions::default(),
+                            ) {
+                                error!("Could not change to Project {target}: {e}");
+                            }
+                        }
+                        Err(WechselError::NotFound(_)) => {
+                            error!("The project {target} of a rule could not be found")
+                        }
+                        Err(e) => error!("{e}"),
+                    }
+                }
+            }
+            if !in_grace_period(config.grace_period) {
+                last_result = result;
+            }
+        }
+
+        thread::sleep(Duration::from_secs(config.interval.max(1)));
+    }
+}
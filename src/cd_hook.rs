@@ -0,0 +1,132 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use dialoguer::Confirm;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::resolve::project_containing;
+use wechsel::{
+    NO_AUTO_SWITCH_MARKER, Workspace, cd_hook_cache_path, find_project_folder, path_from_iter,
+};
+
+// More project folders than anyone visits, the cache starts over after that
+const MAX_RESOLVED: usize = 256;
+
+/// What the shell hook remembers between two calls, so most directory changes don't search the project tree
+#[derive(Default, Serialize, Deserialize)]
+struct CdHookCache {
+    /// The project folder of the last working directory and the project that was active then
+    last: Option<(PathBuf, Option<PathBuf>)>,
+    /// Project folders and the name and path of their project in the tree, `None` for folders outside of it
+    projects: BTreeMap<PathBuf, Option<(String, PathBuf)>>,
+}
+
+impl CdHookCache {
+    fn load(config_dir: &Path) -> CdHookCache {
+        fs::read_to_string(cd_hook_cache_path(config_dir))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, config_dir: &Path) {
+        let cache = cd_hook_cache_path(config_dir);
+        let content = serde_json::to_string(self).unwrap_or_default();
+        if let Err(e) = fs::write(&cache, content) {
+            debug!("Could not write {cache:?}: {e}");
+        }
+    }
+}
+
+/// Called by the shell integration whenever the working directory changes.
+/// Returns the folder of the project that should become active, if any.
+pub fn cd_hook(path: &Path, prompt: bool, workspace: &Workspace) -> Option<PathBuf> {
+    let config_dir = workspace.config_dir().as_path();
+    let mut cache = CdHookCache::load(config_dir);
+    let Some(prj_folder) = find_project_folder(path) else {
+        // Coming back to the last project folder later is handled again
+        if cache.last.take().is_some() {
+            cache.save(config_dir);
+        }
+        return None;
+    };
+
+    // Moving around in the same project folder while the same project is active is a no-op
+    let active = workspace.active_path();
+    let last = (prj_folder.clone(), active.clone());
+    if cache.last.as_ref() == Some(&last) {
+        return None;
+    }
+    cache.last = Some(last);
+
+    if is_same_folder(active.as_deref(), &prj_folder)
+        || path_from_iter([&prj_folder, &PathBuf::from(NO_AUTO_SWITCH_MARKER)]).exists()
+    {
+        cache.save(config_dir);
+        return None;
+    }
+    let Some((prj_name, prj_path)) = resolve(&mut cache, &prj_folder, workspace) else {
+        cache.save(config_dir);
+        return None;
+    };
+
+    // A declined prompt isn't asked again until the folder or the active project changes
+    if prompt
+        && !Confirm::new()
+            .with_prompt(format!("Switch to project {prj_name}?"))
+            .default(true)
+            .interact()
+            .unwrap_or_default()
+    {
+        cache.save(config_dir);
+        return None;
+    }
+
+    // The project is active now, which the next call finds out by itself
+    cache.last = None;
+    cache.save(config_dir);
+    Some(prj_path)
+}
+
+/// Removes what the shell hook remembers, after the project tree changed
+pub fn clear_cd_hook_cache(config_dir: &Path) {
+    let _ = fs::remove_file(cd_hook_cache_path(config_dir));
+}
+
+// The project of a project folder, only folders that are actually part of the project tree count
+fn resolve(
+    cache: &mut CdHookCache,
+    prj_folder: &Path,
+    workspace: &Workspace,
+) -> Option<(String, PathBuf)> {
+    if let Some(project) = cache.projects.get(prj_folder) {
+        return project.clone();
+    }
+    // Names aren't unique, the project is found by its folder
+    let project = prj_folder
+        .canonicalize()
+        .ok()
+        .and_then(|folder| project_containing(&folder, workspace).ok())
+        .filter(|found| is_same_folder(Some(&found.path), prj_folder))
+        .map(|found| (found.name.clone(), found.path.clone()));
+    debug!("Resolved {prj_folder:?} to {project:?}");
+
+    if cache.projects.len() >= MAX_RESOLVED {
+        cache.projects.clear();
+    }
+    cache
+        .projects
+        .insert(prj_folder.to_path_buf(), project.clone());
+    project
+}
+
+fn is_same_folder(a: Option<&Path>, b: &Path) -> bool {
+    a.and_then(|a| a.canonicalize().ok())
+        .zip(b.canonicalize().ok())
+        .map(|(a, b)| a == b)
+        .unwrap_or_default()
+}
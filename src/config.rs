@@ -0,0 +1,400 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{NaiveTime, Weekday};
+use schemars::{JsonSchema, schema_for};
+use serde::{Deserialize, Serialize};
+use toml_edit::DocumentMut;
+
+use crate::conflict::ConflictStrategy;
+use crate::error::WechselError;
+use crate::utils::{
+    CURRENT_PROJECT_FOLDER, DEFAULT_ROOT_PRJ, HOME_FOLDERS, config_file_path, on_prj_change_path,
+    path_from_iter,
+};
+
+#[derive(Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub general: GeneralConfig,
+    pub hooks: HookConfig,
+    pub auto: AutoConfig,
+    pub links: LinkConfig,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeneralConfig {
+    /// Name of the link in the home folder that points to the active project
+    pub current_project_folder: String,
+    /// Name of the root project `wechsel init` creates
+    pub root_project: String,
+    /// The folders of the home folder `wechsel init` offers to move into the root project
+    pub home_folders: Vec<String>,
+    /// The folders `wechsel new` creates, unless others are given
+    pub new_folders: Vec<String>,
+    /// The shells `wechsel init` offers to set up
+    pub shells: Vec<Shell>,
+}
+
+impl Default for GeneralConfig {
+    fn default() -> Self {
+        GeneralConfig {
+            current_project_folder: CURRENT_PROJECT_FOLDER.to_string(),
+            root_project: DEFAULT_ROOT_PRJ.to_string(),
+            home_folders: HOME_FOLDERS.iter().map(|name| name.to_string()).collect(),
+            new_folders: vec!["Desktop".to_string(), "Downloads".to_string()],
+            shells: vec![Shell::Bash, Shell::Fish],
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    Bash,
+    Fish,
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Shell::Bash => write!(f, "Bash"),
+            Shell::Fish => write!(f, "Fish"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct HookConfig {
+    /// Run the scripts in the config dir and the hooks of the projects
+    pub enabled: bool,
+    /// Replaces `on-prj-change` in the config dir, relative paths start in the config dir
+    pub on_prj_change: Option<PathBuf>,
+    /// Replaces `on-prj-create` in the config dir, relative paths start in the config dir
+    pub on_prj_create: Option<PathBuf>,
+    /// Replaces `pre-change` in the config dir, relative paths start in the config dir
+    pub pre_change: Option<PathBuf>,
+    /// Seconds after which a hook is stopped, 0 lets hooks run as long as they need
+    pub timeout: u64,
+}
+
+impl Default for HookConfig {
+    fn default() -> Self {
+        HookConfig {
+            enabled: true,
+            on_prj_change: None,
+            on_prj_create: None,
+            pre_change: None,
+            timeout: 30,
+        }
+    }
+}
+
+impl HookConfig {
+    pub fn on_prj_change_script(&self, config_dir: &PathBuf) -> PathBuf {
+        match &self.on_prj_change {
+            Some(script) => path_from_iter([config_dir, script]),
+            None => on_prj_change_path(config_dir),
+        }
+    }
+
+    pub fn on_prj_create_script(&self, config_dir: &PathBuf) -> PathBuf {
+        match &self.on_prj_create {
+            Some(script) => path_from_iter([config_dir, script]),
+            None => path_from_iter([config_dir, &PathBuf::from("on-prj-create")]),
+        }
+    }
+
+    pub fn pre_change_script(&self, config_dir: &PathBuf) -> PathBuf {
+        match &self.pre_change {
+            Some(script) => path_from_iter([config_dir, script]),
+            None => path_from_iter([config_dir, &PathBuf::from("pre-change")]),
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkConfig {
+    /// What to do with real folders in the place of a link, `skip`, `merge` or `stash`
+    pub conflict: ConflictStrategy,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutoConfig {
+    /// Seconds after a manual change in which rules are not applied
+    pub grace_period: u64,
+    /// Seconds between two checks of the daemon
+    pub interval: u64,
+    pub rules: Vec<Rule>,
+}
+
+impl Default for AutoConfig {
+    fn default() -> Self {
+        AutoConfig {
+            grace_period: 30 * 60,
+            interval: 60,
+            rules: vec![],
+        }
+    }
+}
+
+/// A rule matches if all of its conditions match, conditions that are not set always match
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub project: String,
+    /// Weekdays like `mon` or `friday`
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// Start of the time window as `HH:MM`
+    pub from: Option<String>,
+    /// End of the time window as `HH:MM`, windows can wrap around midnight
+    pub to: Option<String>,
+    /// Shell command that has to succeed
+    pub probe: Option<String>,
+}
+
+impl Rule {
+    /// The weekdays the rule is limited to, empty for every day
+    pub fn weekdays(&self) -> Result<Vec<Weekday>, String> {
+        self.days
+            .iter()
+            .map(|day| {
+                day.parse().map_err(|_| {
+                    format!("Invalid weekday {day:?} in the rule for {}", self.project)
+                })
+            })
+            .collect()
+    }
+
+    /// The start and the end of the time window
+    pub fn window(&self) -> Result<(Option<NaiveTime>, Option<NaiveTime>), String> {
+        let parse = |time: &Option<String>| {
+            time.as_ref()
+                .map(|time| {
+                    NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| {
+                        format!(
+                            "Invalid time {time:?} in the rule for {}, expected HH:MM",
+                            self.project
+                        )
+                    })
+                })
+                .transpose()
+        };
+        Ok((parse(&self.from)?, parse(&self.to)?))
+    }
+}
+
+impl Config {
+    /// Checks the values that can't be checked by their type
+    fn validate(&self) -> Result<(), String> {
+        let general = &self.general;
+        check_name(
+            "general.current_project_folder",
+            &general.current_project_folder,
+        )?;
+        check_name("general.root_project", &general.root_project)?;
+        for folder in general.home_folders.iter() {
+            check_name("general.home_folders", folder)?;
+        }
+        for folder in general.new_folders.iter() {
+            check_name("general.new_folders", folder)?;
+        }
+        if general
+            .home_folders
+            .contains(&general.current_project_folder)
+        {
+            return Err(format!(
+                "general.current_project_folder: {:?} is also in general.home_folders, the links would overwrite each other",
+                general.current_project_folder
+            ));
+        }
+        if self.auto.interval == 0 {
+            return Err("auto.interval: has to be at least 1 second".to_string());
+        }
+        for rule in self.auto.rules.iter() {
+            rule.weekdays()
+                .and_then(|_| rule.window())
+                .map_err(|e| format!("auto.rules: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+// Names end up as a single file name in the home folder or a project folder
+pub(crate) fn check_name(key: &str, name: &str) -> Result<(), String> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(format!("{key}: {name:?} is not a valid folder name"));
+    }
+    if name.contains('/') {
+        return Err(format!("{key}: {name:?} can't contain a /"));
+    }
+    Ok(())
+}
+
+fn parse_config(content: &str, path: &Path) -> Result<Config, WechselError> {
+    let config: Config = toml::from_str(content)
+        .map_err(|e| WechselError::Invalid(format!("Invalid config file {path:?}: {e}")))?;
+    config
+        .validate()
+        .map_err(|e| WechselError::Invalid(format!("Invalid config file {path:?}: {e}")))?;
+    Ok(config)
+}
+
+/// Loads `config.toml` from the config dir, a missing file results in the default config
+pub fn load_config(config_dir: &PathBuf) -> Result<Config, WechselError> {
+    let path = config_file_path(config_dir);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(Config::default());
+    };
+    parse_config(&content, &path)
+}
+
+/// The conflict strategy given on the command line, or the one of the config file
+pub fn conflict_strategy(
+    overridden: Option<ConflictStrategy>,
+    config_dir: &PathBuf,
+) -> Result<ConflictStrategy, WechselError> {
+    match overridden {
+        Some(conflict) => Ok(conflict),
+        None => Ok(load_config(config_dir)?.links.conflict),
+    }
+}
+
+/// Every setting as a dotted key like `general.root_project` with its JSON Schema
+fn settings() -> Vec<(String, serde_json::Value)> {
+    fn collect(
+        prefix: &str,
+        schema: &serde_json::Value,
+        defs: &serde_json::Value,
+        settings: &mut Vec<(String, serde_json::Value)>,
+    ) {
+        let schema = resolve(schema, defs);
+        match schema["properties"].as_object() {
+            Some(properties) => {
+                for (key, property) in properties {
+                    let key = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{prefix}.{key}")
+                    };
+                    collect(&key, property, defs, settings);
+                }
+            }
+            None => settings.push((prefix.to_string(), schema.clone())),
+        }
+    }
+    let schema = serde_json::to_value(schema_for!(Config)).unwrap_or_default();
+    let mut settings = vec![];
+    collect("", &schema, &schema["$defs"], &mut settings);
+    settings
+}
+
+fn resolve<'a>(
+    schema: &'a serde_json::Value,
+    defs: &'a serde_json::Value,
+) -> &'a serde_json::Value {
+    schema["$ref"]
+        .as_str()
+        .and_then(|reference| reference.strip_prefix("#/$defs/"))
+        .and_then(|name| defs.get(name))
+        .unwrap_or(schema)
+}
+
+// Settings that only take strings, their values are never parsed as toml
+fn is_string(schema: &serde_json::Value) -> bool {
+    match &schema["type"] {
+        serde_json::Value::String(kind) => kind == "string",
+        serde_json::Value::Array(kinds) => {
+            kinds.iter().any(|kind| kind == "string")
+                && kinds.iter().all(|kind| kind == "string" || kind == "null")
+        }
+        _ => schema["oneOf"]
+            .as_array()
+            .is_some_and(|variants| variants.iter().all(is_string)),
+    }
+}
+
+/// Every setting as a dotted key like `general.root_project`, including the defaults,
+/// `None` for the ones that are not set
+pub fn config_values(
+    config_dir: &PathBuf,
+) -> Result<Vec<(String, Option<toml::Value>)>, WechselError> {
+    let config = toml::Value::try_from(load_config(config_dir)?)
+        .map_err(|e| WechselError::Invalid(format!("Could not read the config: {e}")))?;
+    Ok(settings()
+        .into_iter()
+        .map(|(key, _)| {
+            let value = key
+                .split('.')
+                .try_fold(&config, |value, name| value.get(name))
+                .cloned();
+            (key, value)
+        })
+        .collect())
+}
+
+/// The value of a setting, the default if the config file doesn't set it and `None` if there is none
+pub fn config_get(key: &str, config_dir: &PathBuf) -> Result<Option<toml::Value>, WechselError> {
+    config_values(config_dir)?
+        .into_iter()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value)
+        .ok_or(WechselError::NotFound(format!(
+            "{key} is not a setting, `wechsel config list` lists all of them"
+        )))
+}
+
+/// Changes a setting in the config file, keeping its formatting and comments.
+/// `value` is parsed as toml, except for settings that are strings.
+pub fn config_set(key: &str, value: &str, config_dir: &PathBuf) -> Result<(), WechselError> {
+    let path = config_file_path(config_dir);
+    let content = fs::read_to_string(&path).unwrap_or_default();
+    let mut document: DocumentMut = content
+        .parse()
+        .map_err(|e| WechselError::Invalid(format!("Invalid config file {path:?}: {e}")))?;
+
+    let is_string = settings()
+        .iter()
+        .any(|(setting, schema)| setting == key && is_string(schema));
+    let value = match value.parse::<toml_edit::Value>() {
+        Ok(value) if !is_string => value,
+        _ => toml_edit::Value::from(value),
+    };
+
+    let (sections, name) = match key.rsplit_once('.') {
+        Some((sections, name)) => (sections.split('.').collect(), name),
+        None => (vec![], key),
+    };
+    let mut table = document.as_table_mut();
+    for section in sections {
+        table = table
+            .entry(section)
+            .or_insert(toml_edit::table())
+            .as_table_mut()
+            .ok_or(WechselError::Invalid(format!(
+                "{section} in {key} is not a section"
+            )))?;
+    }
+    if table
+        .get(name)
+        .map(|item| item.is_table())
+        .unwrap_or_default()
+    {
+        return Err(WechselError::Invalid(format!(
+            "{key} is a section, set one of its keys instead"
+        )));
+    }
+    table[name] = toml_edit::value(value);
+
+    let content = document.to_string();
+    parse_config(&content, &path)?;
+    fs::write(&path, content)?;
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use clap::ValueEnum;
+use log::{debug, info, warn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::file_system::{FileSystem, FileType};
+
+/// What to do when a real folder is in the place a wechsel folder should be linked to
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    /// Leave the folder alone and don't link
+    #[default]
+    Skip,
+    /// Move the contents into the wechsel folder of the project, then link
+    Merge,
+    /// Move the folder aside to a timestamped backup, then link
+    Stash,
+}
+
+/// Clears `target` according to `strategy`, returns false if it is still in the way
+pub fn resolve_conflict(
+    fs: &dyn FileSystem,
+    target: &Path,
+    folder: &Path,
+    strategy: ConflictStrategy,
+) -> io::Result<bool> {
+    match strategy {
+        ConflictStrategy::Skip => Ok(false),
+        ConflictStrategy::Merge if !fs.is_dir(target) => {
+            warn!("{target:?} is not a folder, so it can't be merged into {folder:?}");
+            Ok(false)
+        }
+        ConflictStrategy::Merge => {
+            merge(fs, target, folder)?;
+            Ok(true)
+        }
+        ConflictStrategy::Stash => {
+            let backup = backup_path(fs, target);
+            fs.rename(target, &backup)?;
+            info!("Moved {target:?} aside to {backup:?}");
+            Ok(true)
+        }
+    }
+}
+
+fn merge(fs: &dyn FileSystem, from: &Path, into: &Path) -> io::Result<()> {
+    for entry in fs.read_dir(from)? {
+        let destination = free_path(fs, into, Path::new(entry.file_name().unwrap_or_default()));
+        move_path(fs, &entry, &destination)?;
+        info!("Moved {entry:?} to {destination:?}");
+    }
+    fs.remove_dir(from)?;
+    info!("Removed {from:?} after merging it into {into:?}");
+    Ok(())
+}
+
+// A rename can't cross filesystems, then `from` is copied and removed afterwards
+fn move_path(fs: &dyn FileSystem, from: &Path, to: &Path) -> io::Result<()> {
+    match fs.rename(from, to) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            debug!("{from:?} is on another filesystem than {to:?}, copying it");
+            if let Err(e) = copy_all(fs, from, to) {
+                // Nothing is lost, `from` is still complete
+                let _ = remove_all(fs, to);
+                return Err(e);
+            }
+            remove_all(fs, from)
+        }
+        result => result,
+    }
+}
+
+fn copy_all(fs: &dyn FileSystem, from: &Path, to: &Path) -> io::Result<()> {
+    match fs.symlink_metadata(from)?.file_type {
+        FileType::Symlink => fs.symlink(&fs.read_link(from)?, to),
+        FileType::File => fs.copy_file(from, to),
+        FileType::Dir => {
+            fs.create_dir_all(to)?;
+            for entry in fs.read_dir(from)? {
+                copy_all(fs, &entry, &to.join(entry.file_name().unwrap_or_default()))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn remove_all(fs: &dyn FileSystem, path: &Path) -> io::Result<()> {
+    match fs.symlink_metadata(path)?.file_type {
+        FileType::Dir => {
+            for entry in fs.read_dir(path)? {
+                remove_all(fs, &entry)?;
+            }
+            fs.remove_dir(path)
+        }
+        _ => fs.remove_file(path),
+    }
+}
+
+// `name`, or `name (1)`, `name (2)` ... with the extension kept, whichever doesn't exist in `dir` yet
+pub fn free_path(fs: &dyn FileSystem, dir: &Path, name: &Path) -> PathBuf {
+    let path = dir.join(name);
+    if !fs.exists(&path) && !fs.is_symlink(&path) {
+        return path;
+    }
+
+    let stem = name
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = name
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|idx| dir.join(format!("{stem} ({idx}){extension}")))
+        .find(|path| !fs.exists(path) && !fs.is_symlink(path))
+        .unwrap_or(path)
+}
+
+fn backup_path(fs: &dyn FileSystem, target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let timestamp = Local::now().format("%Y%m%d-%H%M%S");
+    let backup = target.with_file_name(format!("{name}.backup-{timestamp}"));
+    free_path(
+        fs,
+        backup.parent().unwrap_or(Path::new("/")),
+        Path::new(backup.file_name().unwrap_or_default()),
+    )
+}
@@ -0,0 +1,120 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use zbus::{blocking::connection, fdo, interface, object_server::SignalEmitter};
+
+use crate::cd_hook::clear_cd_hook_cache;
+use crate::watch::{TreeWatcher, WatchEvent};
+use wechsel::{TreeOutput, Workspace, get_project_tree, query_active_project};
+
+pub const BUS_NAME: &str = "org.wechsel.Manager";
+pub const OBJECT_PATH: &str = "/org/wechsel/Manager";
+
+struct Manager {
+    config_dir: PathBuf,
+}
+
+#[interface(name = "org.wechsel.Manager")]
+impl Manager {
+    /// The project tree including the folders of each project, in the same format as `wechsel tree --folders`
+    fn list_projects(&self) -> fdo::Result<String> {
+        tree_json(&self.config_dir)
+    }
+
+    fn get_active(&self) -> String {
+        query_active_project(&self.config_dir).unwrap_or_default()
+    }
+
+    fn change(&self, project: &str) -> fdo::Result<()> {
+        run_wechsel(&["change", "--", project])
+    }
+
+    /// Creates the project like `wechsel new` and changes to it
+    fn create_project(&self, project: &str, parent: &str, folders: Vec<String>) -> fdo::Result<()> {
+        let failed = |e: wechsel::WechselError| fdo::Error::Failed(e.to_string());
+        let workspace = Workspace::with_config_dir(&self.config_dir).map_err(failed)?;
+        let parent = workspace.find(parent).map_err(failed)?;
+        let path = workspace
+            .create_in(project, &parent.path, &folders)
+            .map_err(failed)?;
+        clear_cd_hook_cache(&self.config_dir);
+        run_wechsel(&["change", "--", &path.to_string_lossy()])
+    }
+
+    #[zbus(signal)]
+    async fn project_changed(
+        emitter: &SignalEmitter<'_>,
+        project: &str,
+        old_project: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn tree_changed(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+}
+
+fn tree_json(config_dir: &Path) -> fdo::Result<String> {
+    let tree = get_project_tree(config_dir, true).map_err(|e| fdo::Error::Failed(e.to_string()))?;
+    serde_json::to_string(&TreeOutput::new(
+        tree,
+        query_active_project(config_dir).unwrap_or_default(),
+    ))
+    .map_err(|e| fdo::Error::Failed(e.to_string()))
+}
+
+// Changes go through the cli, so they run the same hooks and get recorded in the history.
+// Arguments that come from the bus go after `--`, so they are never taken for options.
+fn run_wechsel(args: &[&str]) -> fdo::Result<()> {
+    let exe = std::env::current_exe().map_err(|e| fdo::Error::Failed(e.to_string()))?;
+    let Output { status, stderr, .. } = Command::new(exe)
+        .args(args)
+        .output()
+        .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(fdo::Error::Failed(
+            String::from_utf8_lossy(&stderr).trim().to_string(),
+        ))
+    }
+}
+
+/// Serves `org.wechsel.Manager` on the session bus until the process is killed.
+/// Changes made outside of the service are picked up by the same watcher as `wechsel watch`.
+pub fn serve(config_dir: &Path) -> zbus::Result<()> {
+    let connection = connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(
+            OBJECT_PATH,
+            Manager {
+                config_dir: config_dir.to_path_buf(),
+            },
+        )?
+        .build()?;
+    let iface = connection
+        .object_server()
+        .interface::<_, Manager>(OBJECT_PATH)?;
+
+    let mut watcher =
+        TreeWatcher::new(config_dir).map_err(|e| zbus::Error::Failure(e.to_string()))?;
+    loop {
+        let events = watcher
+            .next_events()
+            .map_err(|e| zbus::Error::Failure(e.to_string()))?;
+
+        let mut tree_changed = false;
+        for event in events {
+            match event {
+                WatchEvent::ActiveChanged { name, old_name, .. } => zbus::block_on(
+                    Manager::project_changed(iface.signal_emitter(), &name, &old_name),
+                )?,
+                _ => tree_changed = true,
+            }
+        }
+        if tree_changed {
+            zbus::block_on(Manager::tree_changed(iface.signal_emitter()))?;
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fmt, fs, io,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use log::info;
+use serde::Serialize;
+
+use crate::output::say;
+use crate::upgrade::pending_upgrades;
+use wechsel::{
+    Config, ConflictStrategy, FoundProject, HookSettings, LifecycleEvent, OsFileSystem,
+    ProjectLock, ProjectTreeNode, WECHSEL_FOLDER_EXTENSION, WechselError, Workspace, bashrc_path,
+    current_project_folder, env_files, fish_config_path, get_old_config_file_path,
+    get_project_tree, hook_scripts, lifecycle_script, load_config, path_from_iter,
+};
+
+// The shell snippets of older versions sourced these misspelled files
+pub const MISSPELLED_ENV_FILE: &str = "enviroment_variables";
+pub const ENV_FILE: &str = "environment_variables";
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Problem {
+    pub severity: Severity,
+    pub message: String,
+    /// Set if `--fix` repaired the problem
+    pub fixed: bool,
+}
+
+struct Doctor {
+    fix: bool,
+    problems: Vec<Problem>,
+}
+
+impl Doctor {
+    /// Records a problem, `repair` is only called with `--fix` and `None` means it can't be repaired
+    fn report(
+        &mut self,
+        severity: Severity,
+        message: String,
+        repair: Option<&mut dyn FnMut() -> io::Result<()>>,
+    ) {
+        let fixed = match repair {
+            Some(repair) if self.fix => match repair() {
+                Ok(()) => {
+                    info!("Fixed: {message}");
+                    true
+                }
+                Err(e) => {
+                    self.problems.push(Problem {
+                        severity,
+                        message: format!("{message}, fixing it failed: {e}"),
+                        fixed: false,
+                    });
+                    return;
+                }
+            },
+            _ => false,
+        };
+        self.problems.push(Problem {
+            severity,
+            message,
+            fixed,
+        });
+    }
+}
+
+/// Checks the wechsel setup for problems and repairs the ones that can be repaired safely with `fix`
+pub fn doctor(fix: bool, config_dir: &PathBuf) -> Result<Vec<Problem>, WechselError> {
+    let _lock = fix.then(|| ProjectLock::acquire(config_dir)).transpose()?;
+    let workspace = Workspace::with_config_dir(config_dir)?;
+    let mut doctor = Doctor {
+        fix,
+        problems: vec![],
+    };
+
+    if let Some(old_config) = get_old_config_file_path(config_dir) {
+        doctor.report(
+            Severity::Warning,
+            format!("{old_config:?} is left over from wechsel <= 0.2.3, see `wechsel migrate`"),
+            None,
+        );
+    }
+
+    let pending = pending_upgrades(config_dir)?;
+    if let Some(upgrade) = pending.last() {
+        doctor.report(
+            Severity::Warning,
+            format!(
+                "The setup is from an older version of wechsel, `wechsel upgrade` brings it to layout version {}",
+                upgrade.version
+            ),
+            None,
+        );
+    }
+
+    // Everything that reads the project tree needs a valid config file
+    let config = match load_config(config_dir) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            doctor.report(Severity::Error, e.to_string(), None);
+            None
+        }
+    };
+
+    if let Some(config) = &config {
+        check_scripts(&mut doctor, config, config_dir);
+    }
+    check_shell_snippets(&mut doctor, config_dir);
+
+    match config.map(|_| get_project_tree(config_dir, false)) {
+        None => {}
+        Some(Ok(tree)) => check_links(&mut doctor, &workspace, &tree)?,
+        Some(Err(e @ (WechselError::NotInitialized | WechselError::OldSetup))) => {
+            doctor.report(Severity::Error, e.to_string(), None)
+        }
+        Some(Err(e)) => return Err(e),
+    }
+
+    doctor
+        .problems
+        .sort_by_key(|problem| Reverse(problem.severity));
+    if doctor.problems.is_empty() {
+        say!("No problems found");
+    }
+    for problem in doctor.problems.iter() {
+        let fixed = if problem.fixed { " (fixed)" } else { "" };
+        say!("{}: {}{fixed}", problem.severity, problem.message);
+    }
+    Ok(doctor.problems)
+}
+
+fn check_scripts(doctor: &mut Doctor, config: &Config, config_dir: &PathBuf) {
+    let mut scripts = vec![
+        config.hooks.on_prj_change_script(config_dir),
+        config.hooks.on_prj_create_script(config_dir),
+        config.hooks.pre_change_script(config_dir),
+    ];
+    scripts.extend(
+        LifecycleEvent::ALL
+            .iter()
+            .map(|event| lifecycle_script(*event, config_dir)),
+    );
+    for script in scripts
+        .iter()
+        .flat_map(|script| hook_scripts(&OsFileSystem, script))
+    {
+        if let Err(e) = HookSettings::read(&OsFileSystem, &script) {
+            doctor.report(Severity::Warning, e, None);
+        }
+        let Ok(meta) = fs::metadata(&script) else {
+            continue;
+        };
+        if meta.is_file() && meta.permissions().mode() & 0o111 == 0 {
+            doctor.report(
+                Severity::Warning,
+                format!("{script:?} is not executable"),
+                Some(&mut || {
+                    let mut permissions = meta.permissions();
+                    permissions.set_mode(permissions.mode() | 0o111);
+                    fs::set_permissions(&script, permissions)
+                }),
+            );
+        }
+    }
+}
+
+fn check_shell_snippets(doctor: &mut Doctor, config_dir: &PathBuf) {
+    let configs = [bashrc_path(), fish_config_path(config_dir)];
+    let mut integrated = false;
+    for config in configs.iter() {
+        let Ok(content) = fs::read_to_string(config) else {
+            continue;
+        };
+        integrated |= content.contains(ENV_FILE) || content.contains(MISSPELLED_ENV_FILE);
+        if content.contains(MISSPELLED_ENV_FILE) {
+            doctor.report(
+                Severity::Warning,
+                format!(
+                    "The wechsel snippet in {config:?} sources {MISSPELLED_ENV_FILE} instead of {ENV_FILE}, so $PRJ is never set"
+                ),
+                Some(&mut || fs::write(config, content.replace(MISSPELLED_ENV_FILE, ENV_FILE))),
+            );
+        }
+    }
+    if !integrated {
+        doctor.report(
+            Severity::Info,
+            format!(
+                "No shell integration was found in {configs:?}, so $PRJ isn't set in new shells"
+            ),
+            None,
+        );
+    }
+}
+
+fn check_links(
+    doctor: &mut Doctor,
+    workspace: &Workspace,
+    tree: &ProjectTreeNode,
+) -> Result<(), WechselError> {
+    let (home, config_dir) = (workspace.home(), workspace.config_dir());
+    // Links into folders that no longer exist
+    let current_project_folder = current_project_folder(config_dir);
+    for entry in fs::read_dir(home)?.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let Ok(target) = fs::read_link(&path) else {
+            continue;
+        };
+        // ~/Project is checked below
+        let points_into_wechsel = entry.file_name().to_str()
+            != Some(current_project_folder.as_str())
+            && target
+                .extension()
+                .map(|ext| ext == WECHSEL_FOLDER_EXTENSION)
+                .unwrap_or_default();
+        if points_into_wechsel && !path.exists() {
+            doctor.report(
+                Severity::Error,
+                format!("{path:?} points to {target:?}, which doesn't exist"),
+                Some(&mut || fs::remove_file(&path)),
+            );
+        }
+    }
+
+    // The active project, a broken link is left to `wechsel change`, which runs the hooks
+    // and records the change like any other
+    let active_link = workspace.current_project_link();
+    let root = &tree.prj_name;
+    let active = match fs::read_link(&active_link) {
+        Ok(_) => {
+            let active = find_node_by_path(tree, &active_link);
+            if active.is_none() {
+                doctor.report(
+                    Severity::Error,
+                    format!(
+                        "{active_link:?} points outside of the project tree, run `wechsel change {root}` to link the root project"
+                    ),
+                    None,
+                );
+            }
+            active
+        }
+        Err(_) if active_link.exists() => {
+            doctor.report(
+                Severity::Error,
+                format!("{active_link:?} is not a symlink, remove it and change the project"),
+                None,
+            );
+            None
+        }
+        Err(_) => {
+            doctor.report(
+                Severity::Error,
+                format!(
+                    "{active_link:?} doesn't exist, run `wechsel change {root}` to link the root project"
+                ),
+                None,
+            );
+            None
+        }
+    };
+    let Some(active) = active else {
+        return Ok(());
+    };
+    let active = match workspace.find_by_path(&active) {
+        Ok(Some(active)) => active,
+        Ok(None) => return Ok(()),
+        Err(e) => {
+            doctor.report(Severity::Error, e.to_string(), None);
+            return Ok(());
+        }
+    };
+
+    check_folder_links(doctor, workspace, &active);
+
+    let prj_path = active.path.to_str().unwrap_or_default().to_string();
+    // Variables exported by hooks follow the ones of the project
+    for (path, content) in env_files(
+        &OsFileSystem,
+        &active.name,
+        &prj_path,
+        &BTreeMap::new(),
+        config_dir,
+    ) {
+        let current = fs::read_to_string(&path).unwrap_or_default();
+        if current != content && !current.starts_with(&format!("{content}\n")) {
+            doctor.report(
+                Severity::Warning,
+                format!("{path:?} doesn't match the active project {}", active.name),
+                Some(&mut || fs::write(&path, &content)),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_folder_links(doctor: &mut Doctor, workspace: &Workspace, active: &Rc<FoundProject>) {
+    for (name, folder) in workspace.project_links(active) {
+        let link = path_from_iter([workspace.home(), Path::new(&name)]);
+        let mut relink = || {
+            workspace
+                .link_folder(&folder, &name, ConflictStrategy::Skip)
+                .map(|_| ())
+        };
+
+        if let Ok(target) = fs::read_link(&link) {
+            // Dangling links were already reported
+            if link.exists() && !is_same_file(&target, &folder) {
+                doctor.report(
+                    Severity::Warning,
+                    format!("{link:?} points to {target:?} instead of {folder:?}"),
+                    Some(&mut relink),
+                );
+            }
+        } else if !link.exists() {
+            doctor.report(
+                Severity::Warning,
+                format!("{link:?} is missing, it should point to {folder:?}"),
+                Some(&mut relink),
+            );
+        } else if link.is_dir() && is_empty_dir(&link) {
+            doctor.report(
+                Severity::Warning,
+                format!("{link:?} is an empty directory instead of a link to {folder:?}"),
+                Some(&mut || {
+                    fs::remove_dir(&link)?;
+                    relink()
+                }),
+            );
+        } else {
+            doctor.report(
+                Severity::Error,
+                format!(
+                    "{link:?} is not a link to {folder:?}, `wechsel change {} --on-conflict merge` moves its contents into {folder:?}",
+                    active.name
+                ),
+                None,
+            );
+        }
+    }
+}
+
+// The path in the tree of the project whose folder the link points to
+fn find_node_by_path(node: &ProjectTreeNode, link: &Path) -> Option<PathBuf> {
+    if is_same_file(link, &node.path) {
+        return Some(node.path.clone());
+    }
+    node.children
+        .iter()
+        .find_map(|child| find_node_by_path(child, link))
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    a.canonicalize()
+        .ok()
+        .zip(b.canonicalize().ok())
+        .map(|(a, b)| a == b)
+        .unwrap_or_default()
+}
+
+fn is_empty_dir(path: &Path) -> bool {
+    fs::read_dir(path)
+        .map(|mut entries| entries.next().is_none())
+        .unwrap_or_default()
+}
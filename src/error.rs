@@ -0,0 +1,145 @@
+use std::{fmt, io, path::PathBuf};
+
+use serde::{Serialize, ser::SerializeStruct};
+
+pub const EXIT_ERROR: i32 = 1;
+// 2 is used by clap for invalid arguments
+pub const EXIT_NOT_FOUND: i32 = 3;
+pub const EXIT_AMBIGUOUS: i32 = 4;
+pub const EXIT_NOT_INITIALIZED: i32 = 5;
+pub const EXIT_OLD_SETUP: i32 = 6;
+pub const EXIT_CONFLICT: i32 = 7;
+pub const EXIT_HOOK_FAILED: i32 = 8;
+pub const EXIT_LOCK_HELD: i32 = 9;
+pub const EXIT_VETOED: i32 = 10;
+
+#[derive(Debug)]
+pub enum WechselError {
+    /// A project, parent project or path that doesn't exist
+    NotFound(String),
+    /// A project name that matches more than one project
+    Ambiguous(String, Vec<PathBuf>),
+    NotInitialized,
+    /// The home folder is still set up for wechsel <= 0.2.3
+    OldSetup,
+    /// Something on disk is in the way, e.g. a file where a project folder should be created
+    Conflict(String),
+    HookFailed {
+        hook: PathBuf,
+        code: Option<i32>,
+    },
+    /// Another wechsel process is changing the projects right now
+    LockHeld(u32),
+    /// A pre-change hook blocked the change
+    Vetoed {
+        hook: PathBuf,
+        stderr: String,
+    },
+    /// Invalid input, like an unparsable time or a broken config file
+    Invalid(String),
+    /// A prompt was aborted
+    Cancelled,
+    Io(io::Error),
+}
+
+impl WechselError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            WechselError::NotFound(_) => EXIT_NOT_FOUND,
+            WechselError::Ambiguous(..) => EXIT_AMBIGUOUS,
+            WechselError::NotInitialized => EXIT_NOT_INITIALIZED,
+            WechselError::OldSetup => EXIT_OLD_SETUP,
+            WechselError::Conflict(_) => EXIT_CONFLICT,
+            WechselError::HookFailed { .. } => EXIT_HOOK_FAILED,
+            WechselError::LockHeld(_) => EXIT_LOCK_HELD,
+            WechselError::Vetoed { .. } => EXIT_VETOED,
+            WechselError::Invalid(_) | WechselError::Cancelled | WechselError::Io(_) => EXIT_ERROR,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WechselError::NotFound(_) => "not_found",
+            WechselError::Ambiguous(..) => "ambiguous",
+            WechselError::NotInitialized => "not_initialized",
+            WechselError::OldSetup => "old_setup",
+            WechselError::Conflict(_) => "conflict",
+            WechselError::HookFailed { .. } => "hook_failed",
+            WechselError::LockHeld(_) => "lock_held",
+            WechselError::Vetoed { .. } => "vetoed",
+            WechselError::Invalid(_) => "invalid",
+            WechselError::Cancelled => "cancelled",
+            WechselError::Io(_) => "io",
+        }
+    }
+}
+
+impl fmt::Display for WechselError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WechselError::NotFound(msg)
+            | WechselError::Conflict(msg)
+            | WechselError::Invalid(msg) => {
+                write!(f, "{msg}")
+            }
+            WechselError::Ambiguous(name, paths) => write!(
+                f,
+                "The name {name} matches multiple projects: {}",
+                paths
+                    .iter()
+                    .map(|path| format!("{path:?}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            WechselError::NotInitialized => write!(
+                f,
+                "Wechsel could not find any projects in your home directory, are you sure you initialized wechsel already? (wechsel init)"
+            ),
+            WechselError::OldSetup => write!(
+                f,
+                "Your wechsel setup seems to be setup for an old version of wechsel, please migrate to the new wechsel setup by calling wechsel migrate"
+            ),
+            WechselError::HookFailed { hook, code } => match code {
+                Some(code) => write!(f, "The hook {hook:?} failed with exit code {code}"),
+                None => write!(f, "The hook {hook:?} was terminated by a signal"),
+            },
+            WechselError::LockHeld(pid) => write!(
+                f,
+                "Another wechsel process (pid {pid}) is changing projects right now"
+            ),
+            WechselError::Vetoed { hook, stderr } => {
+                write!(f, "The change was blocked by {hook:?}")?;
+                if !stderr.trim().is_empty() {
+                    write!(f, ": {}", stderr.trim())?;
+                }
+                Ok(())
+            }
+            WechselError::Cancelled => write!(f, "Cancelled"),
+            WechselError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for WechselError {}
+
+impl From<io::Error> for WechselError {
+    fn from(e: io::Error) -> Self {
+        WechselError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for WechselError {
+    fn from(e: serde_json::Error) -> Self {
+        WechselError::Io(e.into())
+    }
+}
+
+impl Serialize for WechselError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("WechselError", 3)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.serialize_field("exit_code", &self.exit_code())?;
+        error.end()
+    }
+}
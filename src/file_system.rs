@@ -0,0 +1,505 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    ffi::OsString,
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// The filesystem operations wechsel needs for the home folder, the projects in it,
+/// the files it writes to the config dir and the time log.
+/// `config.toml` and the lock are always on disk, other processes share them.
+pub trait FileSystem: fmt::Debug {
+    /// The paths of the entries of a folder, like the `path()` of `std::fs::read_dir`
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+    /// Follows symlinks
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata>;
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    /// Only removes empty folders
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Copies a file, for moving it to another filesystem
+    fn copy_file(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// The content of a file, follows symlinks
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    /// Creates or replaces a file
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    /// Adds to the end of a file, creates it if it doesn't exist
+    fn append(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.metadata(path)
+            .map(|metadata| metadata.file_type == FileType::Dir)
+            .unwrap_or(false)
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        self.symlink_metadata(path)
+            .map(|metadata| metadata.file_type == FileType::Symlink)
+            .unwrap_or(false)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.metadata(path)
+            .map(|metadata| metadata.file_type == FileType::File)
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileType {
+    Dir,
+    File,
+    Symlink,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Metadata {
+    pub file_type: FileType,
+}
+
+impl From<fs::FileType> for Metadata {
+    fn from(file_type: fs::FileType) -> Self {
+        let file_type = if file_type.is_symlink() {
+            FileType::Symlink
+        } else if file_type.is_dir() {
+            FileType::Dir
+        } else {
+            FileType::File
+        };
+        Metadata { file_type }
+    }
+}
+
+/// The real filesystem
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OsFileSystem;
+
+impl FileSystem for OsFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        Ok(fs::metadata(path)?.file_type().into())
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        Ok(fs::symlink_metadata(path)?.file_type().into())
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, link)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn copy_file(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::copy(from, to).map(|_| ())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn append(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(contents)
+    }
+}
+
+/// The operations of a [`FileSystem`], for injecting errors into a [`MemoryFileSystem`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    ReadDir,
+    Metadata,
+    ReadLink,
+    Symlink,
+    Rename,
+    RemoveFile,
+    RemoveDir,
+    CreateDir,
+    CopyFile,
+    Read,
+    Write,
+    Append,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Dir,
+    File(Vec<u8>),
+    Symlink(PathBuf),
+}
+
+/// A filesystem that only exists in memory, for testing the project logic without touching the disk.
+/// Paths have to be absolute, `/` exists from the start.
+#[derive(Debug)]
+pub struct MemoryFileSystem {
+    nodes: RefCell<BTreeMap<PathBuf, Node>>,
+    failures: RefCell<Vec<(Operation, PathBuf, Option<i32>)>>,
+}
+
+impl Default for MemoryFileSystem {
+    fn default() -> Self {
+        MemoryFileSystem {
+            nodes: RefCell::new(BTreeMap::from([(PathBuf::from("/"), Node::Dir)])),
+            failures: RefCell::new(vec![]),
+        }
+    }
+}
+
+// Symlink loops end here, like ELOOP
+const MAX_SYMLINKS: usize = 40;
+
+impl MemoryFileSystem {
+    pub fn new() -> MemoryFileSystem {
+        MemoryFileSystem::default()
+    }
+
+    /// Creates an empty file and the folders leading to it
+    pub fn add_file(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            self.create_dir_all(parent)?;
+        }
+        let path = self.resolve_parent(path)?;
+        if self.nodes.borrow().contains_key(&path) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        self.nodes.borrow_mut().insert(path, Node::File(vec![]));
+        Ok(())
+    }
+
+    /// Lets every `operation` on `path` fail from now on, until [`MemoryFileSystem::clear_failures`]
+    pub fn fail(&self, operation: Operation, path: impl Into<PathBuf>) {
+        self.failures
+            .borrow_mut()
+            .push((operation, path.into(), None));
+    }
+
+    /// Like [`MemoryFileSystem::fail`] with an error of the OS, like `libc::EXDEV` for a rename to another filesystem
+    pub fn fail_with_os_error(&self, operation: Operation, path: impl Into<PathBuf>, code: i32) {
+        self.failures
+            .borrow_mut()
+            .push((operation, path.into(), Some(code)));
+    }
+
+    pub fn clear_failures(&self) {
+        self.failures.borrow_mut().clear();
+    }
+
+    fn check(&self, operation: Operation, path: &Path) -> io::Result<()> {
+        let failures = self.failures.borrow();
+        let Some((_, _, code)) = failures
+            .iter()
+            .find(|(failing, failing_path, _)| *failing == operation && failing_path == path)
+        else {
+            return Ok(());
+        };
+        Err(match code {
+            Some(code) => io::Error::from_raw_os_error(*code),
+            None => io::Error::other(format!("Injected {operation:?} failure for {path:?}")),
+        })
+    }
+
+    fn node(&self, path: &Path) -> Option<Node> {
+        self.nodes.borrow().get(path).cloned()
+    }
+
+    /// The path with all symlinks resolved, the last component is only resolved with `follow`
+    fn resolve_with(&self, path: &Path, follow: bool) -> io::Result<PathBuf> {
+        if !path.is_absolute() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{path:?} is not absolute"),
+            ));
+        }
+        // The next component is at the end
+        let mut pending: Vec<OsString> = components(path);
+        let mut resolved = PathBuf::from("/");
+        let mut followed = 0;
+        while let Some(name) = pending.pop() {
+            if name == "/" || name == "." {
+                continue;
+            }
+            if name == ".." {
+                resolved.pop();
+                continue;
+            }
+            resolved.push(&name);
+            if let Some(Node::Symlink(target)) = self.node(&resolved) {
+                if pending.is_empty() && !follow {
+                    break;
+                }
+                followed += 1;
+                if followed > MAX_SYMLINKS {
+                    return Err(io::Error::other(format!("Too many links in {path:?}")));
+                }
+                // The rest of the path continues in the target
+                resolved.pop();
+                if target.is_absolute() {
+                    resolved = PathBuf::from("/");
+                }
+                pending.extend(components(&target));
+            }
+        }
+        Ok(resolved)
+    }
+
+    fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+        self.resolve_with(path, true)
+    }
+
+    fn resolve_parent(&self, path: &Path) -> io::Result<PathBuf> {
+        self.resolve_with(path, false)
+    }
+
+    fn existing_dir(&self, path: &Path) -> io::Result<PathBuf> {
+        let resolved = self.resolve(path)?;
+        match self.node(&resolved) {
+            Some(Node::Dir) => Ok(resolved),
+            Some(_) => Err(io::Error::other(format!("{path:?} is not a directory"))),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn parent_dir(&self, path: &Path) -> io::Result<PathBuf> {
+        self.existing_dir(path.parent().unwrap_or(Path::new("/")))
+    }
+
+    // Creates or replaces the file at `path`, a link in its place is followed
+    fn write_file(&self, path: &Path, contents: Vec<u8>) -> io::Result<()> {
+        self.parent_dir(path)?;
+        let path = self.resolve(path)?;
+        if matches!(self.node(&path), Some(Node::Dir)) {
+            return Err(io::Error::other(format!("{path:?} is a directory")));
+        }
+        self.nodes.borrow_mut().insert(path, Node::File(contents));
+        Ok(())
+    }
+}
+
+// In reverse, so they can be popped
+fn components(path: &Path) -> Vec<OsString> {
+    path.components()
+        .rev()
+        .map(|component| component.as_os_str().to_os_string())
+        .collect()
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.check(Operation::ReadDir, path)?;
+        let resolved = self.existing_dir(path)?;
+        Ok(self
+            .nodes
+            .borrow()
+            .keys()
+            .filter(|child| child.parent() == Some(resolved.as_path()))
+            .filter_map(|child| child.file_name().map(|name| path.join(name)))
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.check(Operation::Metadata, path)?;
+        let resolved = self.resolve(path)?;
+        metadata_of(self.node(&resolved))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.check(Operation::Metadata, path)?;
+        let resolved = self.resolve_parent(path)?;
+        metadata_of(self.node(&resolved))
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        self.check(Operation::ReadLink, path)?;
+        match self.node(&self.resolve_parent(path)?) {
+            Some(Node::Symlink(target)) => Ok(target),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{path:?} is not a symlink"),
+            )),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        self.check(Operation::Symlink, link)?;
+        self.parent_dir(link)?;
+        let link = self.resolve_parent(link)?;
+        if self.nodes.borrow().contains_key(&link) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        self.nodes
+            .borrow_mut()
+            .insert(link, Node::Symlink(target.to_path_buf()));
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check(Operation::Rename, from)?;
+        self.check(Operation::Rename, to)?;
+        let from = self.resolve_parent(from)?;
+        self.parent_dir(to)?;
+        let to = self.resolve_parent(to)?;
+        if self.node(&from).is_none() {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        if to.starts_with(&from) && to != from {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Can't move {from:?} into itself"),
+            ));
+        }
+        let mut nodes = self.nodes.borrow_mut();
+        if nodes
+            .keys()
+            .any(|path| path.starts_with(&to) && path != &to)
+        {
+            return Err(io::Error::other(format!("{to:?} is not empty")));
+        }
+        let moved: Vec<PathBuf> = nodes
+            .keys()
+            .filter(|path| path.starts_with(&from))
+            .cloned()
+            .collect();
+        for path in moved {
+            let node = nodes.remove(&path).unwrap();
+            let new_path = to.join(path.strip_prefix(&from).unwrap());
+            nodes.insert(new_path, node);
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.check(Operation::RemoveFile, path)?;
+        let resolved = self.resolve_parent(path)?;
+        match self.node(&resolved) {
+            Some(Node::File(_) | Node::Symlink(_)) => {
+                self.nodes.borrow_mut().remove(&resolved);
+                Ok(())
+            }
+            Some(Node::Dir) => Err(io::Error::other(format!("{path:?} is a directory"))),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        self.check(Operation::RemoveDir, path)?;
+        let resolved = self.resolve_parent(path)?;
+        match self.node(&resolved) {
+            Some(Node::Dir) => {}
+            Some(_) => return Err(io::Error::other(format!("{path:?} is not a directory"))),
+            None => return Err(io::ErrorKind::NotFound.into()),
+        }
+        let mut nodes = self.nodes.borrow_mut();
+        if nodes
+            .keys()
+            .any(|child| child.parent() == Some(resolved.as_path()))
+        {
+            return Err(io::Error::other(format!("{path:?} is not empty")));
+        }
+        nodes.remove(&resolved);
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        self.check(Operation::CreateDir, path)?;
+        for ancestor in path.ancestors().collect::<Vec<_>>().into_iter().rev() {
+            let resolved = self.resolve(ancestor)?;
+            match self.node(&resolved) {
+                Some(Node::Dir) => {}
+                Some(_) => {
+                    return Err(io::Error::other(format!("{ancestor:?} is not a directory")));
+                }
+                None => {
+                    self.nodes.borrow_mut().insert(resolved, Node::Dir);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn copy_file(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.check(Operation::CopyFile, from)?;
+        let contents = match self.node(&self.resolve(from)?) {
+            Some(Node::File(contents)) => contents,
+            Some(_) => return Err(io::Error::other(format!("{from:?} is not a file"))),
+            None => return Err(io::ErrorKind::NotFound.into()),
+        };
+        self.write_file(to, contents)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.check(Operation::Read, path)?;
+        match self.node(&self.resolve(path)?) {
+            Some(Node::File(contents)) => Ok(contents),
+            Some(_) => Err(io::Error::other(format!("{path:?} is not a file"))),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.check(Operation::Write, path)?;
+        self.write_file(path, contents.to_vec())
+    }
+
+    fn append(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        self.check(Operation::Append, path)?;
+        let mut file = match self.read(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+        file.extend_from_slice(contents);
+        self.write_file(path, file)
+    }
+}
+
+fn metadata_of(node: Option<Node>) -> io::Result<Metadata> {
+    let file_type = match node {
+        Some(Node::Dir) => FileType::Dir,
+        Some(Node::File(_)) => FileType::File,
+        Some(Node::Symlink(_)) => FileType::Symlink,
+        None => return Err(io::ErrorKind::NotFound.into()),
+    };
+    Ok(Metadata { file_type })
+}
@@ -0,0 +1,116 @@
+use std::{io, path::PathBuf};
+
+use chrono::Local;
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::file_system::FileSystem;
+use crate::utils::history_path;
+
+const MAX_HISTORY_LEN: usize = 100;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Navigation {
+    Switch,
+    Back,
+    Forward,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct History {
+    pub switches: Vec<SwitchEntry>,
+    pub back: Vec<String>,
+    pub forward: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SwitchEntry {
+    pub from: String,
+    pub to: String,
+    // Unix timestamp in seconds
+    pub time: i64,
+}
+
+impl History {
+    pub fn load(fs: &dyn FileSystem, config_dir: &PathBuf) -> History {
+        fs.read(&history_path(config_dir))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, fs: &dyn FileSystem, config_dir: &PathBuf) -> io::Result<()> {
+        fs.write(&history_path(config_dir), &serde_json::to_vec(self)?)?;
+        debug!("Wrote {:?}", history_path(config_dir));
+        Ok(())
+    }
+
+    // The project that was active before the current one
+    pub fn previous(&self) -> Option<&str> {
+        self.switches.last().map(|entry| entry.from.as_str())
+    }
+
+    pub fn record(&mut self, from: &str, to: &str, navigation: Navigation) {
+        if from == to {
+            return;
+        }
+        self.switches.push(SwitchEntry {
+            from: from.to_string(),
+            to: to.to_string(),
+            time: Local::now().timestamp(),
+        });
+
+        if !from.is_empty() {
+            match navigation {
+                Navigation::Switch => {
+                    self.back.push(from.to_string());
+                    self.forward.clear();
+                }
+                Navigation::Back => {
+                    self.back.pop();
+                    self.forward.push(from.to_string());
+                }
+                Navigation::Forward => {
+                    self.forward.pop();
+                    self.back.push(from.to_string());
+                }
+            }
+        }
+
+        let overflow = self.switches.len().saturating_sub(MAX_HISTORY_LEN);
+        self.switches.drain(..overflow);
+        for list in [&mut self.back, &mut self.forward] {
+            let overflow = list.len().saturating_sub(MAX_HISTORY_LEN);
+            list.drain(..overflow);
+        }
+    }
+
+    /// Replaces the old name of a renamed project, so `back` and `forward` still find it.
+    /// Returns whether the name was in the history.
+    pub fn rename(&mut self, old: &str, new: &str) -> bool {
+        let mut changed = false;
+        let names = self
+            .switches
+            .iter_mut()
+            .flat_map(|entry| [&mut entry.from, &mut entry.to])
+            .chain(self.back.iter_mut())
+            .chain(self.forward.iter_mut());
+        for name in names.filter(|name| *name == old) {
+            *name = new.to_string();
+            changed = true;
+        }
+        changed
+    }
+}
+
+pub fn record_switch(
+    fs: &dyn FileSystem,
+    from: &str,
+    to: &str,
+    navigation: Navigation,
+    config_dir: &PathBuf,
+) -> io::Result<()> {
+    let mut history = History::load(fs, config_dir);
+    history.record(from, to, navigation);
+    history.save(fs, config_dir)
+}
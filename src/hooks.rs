@@ -0,0 +1,949 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
+    mem,
+    os::{
+        fd::{AsRawFd, FromRawFd},
+        unix::process::CommandExt,
+    },
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    ptr,
+    rc::Rc,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicI32, Ordering},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::config::HookConfig;
+use crate::error::WechselError;
+use crate::file_system::FileSystem;
+use crate::tree::FoundProject;
+use crate::trust::TrustStore;
+use crate::utils::{get_state_dir, log_file_path, path_from_iter};
+
+/// Runs in a project folder when it becomes active or one of its descendants does
+pub const ON_ENTER_HOOK: &str = ".on-enter";
+/// Runs in a project folder when neither it nor one of its descendants is active anymore
+pub const ON_LEAVE_HOOK: &str = ".on-leave";
+/// Runs in a project folder before it is left, a non zero exit status blocks the change
+pub const PRE_CHANGE_HOOK: &str = ".pre-change";
+
+/// Version of the `HookEvent` document, changes when fields are removed or change their meaning
+pub const HOOK_PROTOCOL_VERSION: u32 = 1;
+
+// How long the output of a finished hook is still read
+const OUTPUT_GRACE_PERIOD: Duration = Duration::from_millis(100);
+
+/// Settings a hook can give in a `# wechsel: timeout=10 background continue-on-failure` line of its header
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HookSettings {
+    /// The hook is stopped and counts as failed after this time
+    pub timeout: Option<Duration>,
+    /// Don't wait for the hook, its exit status isn't checked
+    pub background: bool,
+    /// A failure is only reported, the following hooks still run
+    pub continue_on_failure: bool,
+    /// The hook gets a `HookEvent` on stdin and can answer with a `HookResponse` on stdout
+    pub json: bool,
+}
+
+impl HookSettings {
+    /// Reads the settings from the comments at the top of `script`
+    pub fn read(fs: &dyn FileSystem, script: &Path) -> Result<HookSettings, String> {
+        let content = fs
+            .read(script)
+            .map_err(|e| format!("Could not read {script:?}: {e}"))?;
+        HookSettings::parse(script, &content)
+    }
+
+    /// The settings in the header of `content`, which was read from `script`
+    pub fn parse(script: &Path, content: &[u8]) -> Result<HookSettings, String> {
+        let content = String::from_utf8_lossy(content);
+        let mut settings = HookSettings::default();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some(comment) = line.strip_prefix('#') else {
+                break;
+            };
+            let Some(options) = comment.trim().strip_prefix("wechsel:") else {
+                continue;
+            };
+            for option in options.split([' ', ',']).filter(|o| !o.is_empty()) {
+                settings
+                    .set(option)
+                    .map_err(|e| format!("{script:?}: {e}"))?;
+            }
+        }
+        Ok(settings)
+    }
+
+    fn set(&mut self, option: &str) -> Result<(), String> {
+        match option.split_once('=') {
+            None if option == "background" => self.background = true,
+            None if option == "continue-on-failure" => self.continue_on_failure = true,
+            None if option == "json" => self.json = true,
+            Some(("timeout", value)) => {
+                let seconds: f64 = value
+                    .trim_end_matches('s')
+                    .parse()
+                    .ok()
+                    .filter(|s: &f64| s.is_finite() && *s > 0.0)
+                    .ok_or_else(|| format!("The timeout {value} is not a number of seconds"))?;
+                self.timeout = Some(Duration::from_secs_f64(seconds));
+            }
+            _ => return Err(format!("Unknown hook option {option}")),
+        }
+        Ok(())
+    }
+}
+
+/// What happened when a hook ran
+#[derive(Clone, Debug, Serialize)]
+pub struct HookRun {
+    pub hook: PathBuf,
+    /// Missing if the hook runs in the background, timed out or was killed by a signal
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    pub background: bool,
+    /// Empty for hooks that run in the background, their output goes to the log file
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl HookRun {
+    pub fn failed(&self) -> bool {
+        !self.background && self.exit_code != Some(0)
+    }
+}
+
+/// A project as hooks of the JSON protocol see it
+#[derive(Clone, Debug, Serialize)]
+pub struct HookProject {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl From<&Rc<FoundProject>> for HookProject {
+    fn from(prj: &Rc<FoundProject>) -> Self {
+        HookProject {
+            name: prj.name.clone(),
+            path: prj.path.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkAction {
+    Created,
+    Removed,
+}
+
+/// A link in the home folder that was created or removed, `source` is the folder it points to
+#[derive(Clone, Debug, Serialize)]
+pub struct LinkChange {
+    pub action: LinkAction,
+    pub link: PathBuf,
+    pub source: PathBuf,
+}
+
+/// The document hooks of the JSON protocol get on stdin
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct HookEvent {
+    pub version: u32,
+    /// The hook that runs, like `on-prj-change` or `on-enter`
+    pub event: String,
+    /// The new or created project
+    pub project: Option<HookProject>,
+    pub old_project: Option<HookProject>,
+    /// From the root project down to the old project
+    pub old_chain: Vec<HookProject>,
+    /// From the root project down to the new project
+    pub new_chain: Vec<HookProject>,
+    /// Empty for the hooks that run before the links are changed
+    pub links: Vec<LinkChange>,
+    /// The project a hook in a project folder belongs to
+    pub hook_project: Option<HookProject>,
+    /// The wechsel folder of `on-folder-add` and `on-folder-remove`
+    pub folder: Option<String>,
+}
+
+impl HookEvent {
+    pub fn new(event: &str) -> HookEvent {
+        HookEvent {
+            version: HOOK_PROTOCOL_VERSION,
+            event: event.to_string(),
+            ..HookEvent::default()
+        }
+    }
+}
+
+/// Changes to the project tree besides changing and creating projects,
+/// each runs the script of its name in the config dir and the ones in its `.d` directory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LifecycleEvent {
+    Delete,
+    Rename,
+    Move,
+    Archive,
+    FolderAdd,
+    FolderRemove,
+}
+
+impl LifecycleEvent {
+    pub const ALL: [LifecycleEvent; 6] = [
+        LifecycleEvent::Delete,
+        LifecycleEvent::Rename,
+        LifecycleEvent::Move,
+        LifecycleEvent::Archive,
+        LifecycleEvent::FolderAdd,
+        LifecycleEvent::FolderRemove,
+    ];
+
+    /// The name of the script and the event of the JSON protocol
+    pub fn name(self) -> &'static str {
+        match self {
+            LifecycleEvent::Delete => "on-prj-delete",
+            LifecycleEvent::Rename => "on-prj-rename",
+            LifecycleEvent::Move => "on-prj-move",
+            LifecycleEvent::Archive => "on-prj-archive",
+            LifecycleEvent::FolderAdd => "on-folder-add",
+            LifecycleEvent::FolderRemove => "on-folder-remove",
+        }
+    }
+}
+
+/// The script of a lifecycle event, it can't be replaced in the config and is always named after the event
+pub fn lifecycle_script(event: LifecycleEvent, config_dir: &PathBuf) -> PathBuf {
+    path_from_iter([config_dir, &PathBuf::from(event.name())])
+}
+
+/// What hooks of the JSON protocol can print to stdout
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HookResponse {
+    /// Exported in the shells through the environment files
+    pub env: BTreeMap<String, String>,
+    /// Shown to the user
+    pub messages: Vec<String>,
+}
+
+/// The script at `path` and then the ones in the `path.d` directory in lexical order.
+/// Hidden files and backups ending with `~` in the directory are skipped.
+pub fn hook_scripts(fs: &dyn FileSystem, path: &Path) -> Vec<PathBuf> {
+    let mut scripts = vec![];
+    if fs.is_file(path) {
+        scripts.push(path.to_path_buf());
+    }
+    let mut dir = path.as_os_str().to_owned();
+    dir.push(".d");
+    if let Ok(entries) = fs.read_dir(Path::new(&dir)) {
+        let mut entries: Vec<PathBuf> = entries
+            .into_iter()
+            .filter(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                !name.starts_with('.') && !name.ends_with('~') && fs.is_file(path)
+            })
+            .collect();
+        entries.sort();
+        scripts.extend(entries);
+    }
+    scripts
+}
+
+// A hook to run. Allowed project scripts carry the content their hash was checked on,
+// they run from a copy of it in memory so the file can't be swapped in between.
+struct HookScript {
+    path: PathBuf,
+    content: Option<Vec<u8>>,
+}
+
+impl HookScript {
+    fn settings(&self, fs: &dyn FileSystem) -> HookSettings {
+        let settings = match &self.content {
+            Some(content) => HookSettings::parse(&self.path, content),
+            None => HookSettings::read(fs, &self.path),
+        };
+        settings.unwrap_or_else(|e| {
+            warn!("{e}, running it with the default settings");
+            HookSettings::default()
+        })
+    }
+
+    // The command and the file in memory it runs from, which has to stay open until it was spawned
+    fn command(&self) -> io::Result<(Command, Option<File>)> {
+        let Some(content) = &self.content else {
+            let content = fs::read(&self.path).unwrap_or_default();
+            return Ok((script_command(&content, &self.path), None));
+        };
+        let file = memory_file(content)?;
+        let path = PathBuf::from(format!("/dev/fd/{}", file.as_raw_fd()));
+        Ok((script_command(content, &path), Some(file)))
+    }
+}
+
+fn hook_scripts_at(fs: &dyn FileSystem, path: &Path) -> Vec<HookScript> {
+    hook_scripts(fs, path)
+        .into_iter()
+        .map(|path| HookScript {
+            path,
+            content: None,
+        })
+        .collect()
+}
+
+// A file that only exists in memory, child processes inherit it and can open it through /dev/fd
+fn memory_file(content: &[u8]) -> io::Result<File> {
+    // SAFETY: the name is nul terminated
+    let fd = unsafe { libc::memfd_create(b"wechsel-hook\0".as_ptr().cast(), 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: the fd was just created and nothing else owns it
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(content)?;
+    Ok(file)
+}
+
+// Runs the interpreter of the shebang line in `content` with `script`, scripts without one run with `sh`
+fn script_command(content: &[u8], script: &Path) -> Command {
+    let first_line = content.split(|b| *b == b'\n').next().unwrap_or_default();
+    let first_line = String::from_utf8_lossy(first_line);
+    let mut command = match first_line.strip_prefix("#!").map(str::trim) {
+        Some(shebang) if !shebang.is_empty() => {
+            // Like the kernel, everything after the interpreter is a single argument
+            let (interpreter, argument) = match shebang.split_once(char::is_whitespace) {
+                Some((interpreter, argument)) => (interpreter, Some(argument.trim())),
+                None => (shebang, None),
+            };
+            let mut command = Command::new(interpreter);
+            command.args(argument);
+            command
+        }
+        _ => Command::new("sh"),
+    };
+    command.arg(script);
+    command
+}
+
+/// What a hook printed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HookOutput {
+    Stdout,
+    Stderr,
+    /// A message a hook of the JSON protocol answered with
+    Message,
+}
+
+/// Gets the output of hooks line by line while they run, so the caller decides what to show.
+/// The default one shows nothing, the output is still in the `HookRun`s and the log.
+#[derive(Clone)]
+pub struct HookOutputSink(Arc<ShowOutput>);
+
+type ShowOutput = dyn Fn(&Path, HookOutput, &str) + Send + Sync;
+
+impl HookOutputSink {
+    pub fn new(show: impl Fn(&Path, HookOutput, &str) + Send + Sync + 'static) -> HookOutputSink {
+        HookOutputSink(Arc::new(show))
+    }
+
+    fn show(&self, hook: &Path, output: HookOutput, text: &str) {
+        (self.0)(hook, output, text)
+    }
+}
+
+impl Default for HookOutputSink {
+    fn default() -> Self {
+        HookOutputSink::new(|_, _, _| {})
+    }
+}
+
+impl fmt::Debug for HookOutputSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HookOutputSink")
+    }
+}
+
+// The process group of the hook wechsel waits for and the last signal that was passed on to it
+static HOOK_GROUP: AtomicI32 = AtomicI32::new(0);
+static FORWARDED: AtomicI32 = AtomicI32::new(0);
+const FORWARDED_SIGNALS: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+extern "C" fn forward_signal(signal: libc::c_int) {
+    FORWARDED.store(signal, Ordering::SeqCst);
+    let group = HOOK_GROUP.load(Ordering::SeqCst);
+    if group > 0 {
+        // SAFETY: kill is async signal safe, the group is the one of the hook
+        unsafe { libc::kill(-group, signal) };
+    }
+}
+
+// Hooks run in their own process group, so they can be stopped with everything they started.
+// While wechsel waits for one, Ctrl-C and SIGTERM are passed on to its group and afterwards
+// reach wechsel itself, like they would if the hook ran in the group of wechsel.
+struct SignalForwarding {
+    previous: Vec<(libc::c_int, libc::sigaction)>,
+}
+
+impl SignalForwarding {
+    fn install() -> SignalForwarding {
+        FORWARDED.store(0, Ordering::SeqCst);
+        let previous = FORWARDED_SIGNALS
+            .iter()
+            .map(|&signal| {
+                // SAFETY: the handler only uses async signal safe functions and atomics
+                unsafe {
+                    let mut action: libc::sigaction = mem::zeroed();
+                    action.sa_sigaction =
+                        forward_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+                    action.sa_flags = libc::SA_RESTART;
+                    libc::sigemptyset(&mut action.sa_mask);
+                    let mut previous: libc::sigaction = mem::zeroed();
+                    libc::sigaction(signal, &action, &mut previous);
+                    (signal, previous)
+                }
+            })
+            .collect();
+        SignalForwarding { previous }
+    }
+
+    fn forward_to(&self, group: u32) {
+        HOOK_GROUP.store(group as i32, Ordering::SeqCst);
+        // A signal that came while the hook was started
+        let signal = FORWARDED.load(Ordering::SeqCst);
+        if signal != 0 {
+            // SAFETY: kill has no memory safety requirements, the group is the one of the hook
+            unsafe { libc::kill(-(group as i32), signal) };
+        }
+    }
+}
+
+impl Drop for SignalForwarding {
+    fn drop(&mut self) {
+        HOOK_GROUP.store(0, Ordering::SeqCst);
+        for (signal, previous) in self.previous.iter() {
+            // SAFETY: restores the action that was set before
+            unsafe { libc::sigaction(*signal, previous, ptr::null_mut()) };
+        }
+        let signal = FORWARDED.swap(0, Ordering::SeqCst);
+        if signal != 0 {
+            info!("Passing on signal {signal} after the hook stopped");
+            // SAFETY: raise has no memory safety requirements
+            unsafe { libc::raise(signal) };
+        }
+    }
+}
+
+// Logs and captures each line of the output of a hook and passes it on to `sink` if there is one,
+// until every process that holds the pipe closed it
+fn forward_output<R: Read + Send + 'static>(
+    pipe: R,
+    hook: PathBuf,
+    sink: Option<(HookOutputSink, HookOutput)>,
+    captured: Arc<Mutex<String>>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let name = hook.file_name().unwrap_or_default().to_string_lossy();
+        let mut reader = BufReader::new(pipe);
+        let mut line = vec![];
+        while reader
+            .read_until(b'\n', &mut line)
+            .is_ok_and(|read| read > 0)
+        {
+            let text = String::from_utf8_lossy(&line);
+            debug!("{name}: {}", text.trim_end());
+            if let Some((sink, output)) = &sink {
+                sink.show(&hook, *output, &text);
+            }
+            if let Ok(mut captured) = captured.lock() {
+                captured.push_str(&text);
+            }
+            line.clear();
+        }
+    })
+}
+
+/// Runs hooks with the settings of the config and remembers how each of them ended
+pub struct HookRunner {
+    /// Used for hooks that don't set a timeout of their own
+    timeout: Option<Duration>,
+    /// The output of background hooks is appended to this file
+    log_file: Option<PathBuf>,
+    runs: Vec<HookRun>,
+    env: BTreeMap<String, String>,
+    messages: Vec<String>,
+    /// Hooks in project folders only run if they were allowed
+    trust: TrustStore,
+    output: HookOutputSink,
+    fs: Rc<dyn FileSystem>,
+}
+
+impl HookRunner {
+    /// Looks for hooks and the hashes they were allowed with in `fs`
+    pub fn new(config: &HookConfig, config_dir: &PathBuf, fs: Rc<dyn FileSystem>) -> HookRunner {
+        HookRunner {
+            timeout: (config.timeout > 0).then(|| Duration::from_secs(config.timeout)),
+            log_file: get_state_dir().map(|state_dir| log_file_path(&state_dir)),
+            runs: vec![],
+            env: BTreeMap::new(),
+            messages: vec![],
+            trust: TrustStore::load(&*fs, config_dir),
+            output: HookOutputSink::default(),
+            fs,
+        }
+    }
+
+    /// Passes the output of the hooks to `output` while they run
+    pub fn with_output(mut self, output: HookOutputSink) -> HookRunner {
+        self.output = output;
+        self
+    }
+
+    /// Every hook that ran so far, in order
+    pub fn into_runs(self) -> Vec<HookRun> {
+        self.runs
+    }
+
+    /// The variables hooks asked to export, later hooks override earlier ones
+    pub fn env(&self) -> &BTreeMap<String, String> {
+        &self.env
+    }
+
+    /// The messages of the hooks, they were already passed to the output sink
+    pub fn messages(&self) -> &[String] {
+        &self.messages
+    }
+
+    /// Runs the hooks at `path` and in `path.d`.
+    /// A failing hook stops the ones after it unless it continues on failure, its failure is returned.
+    pub fn run_hooks(
+        &mut self,
+        path: &Path,
+        event: &HookEvent,
+        env_vars: &HashMap<String, String>,
+        current_dir: Option<&Path>,
+    ) -> Result<(), WechselError> {
+        self.run_scripts(
+            hook_scripts_at(&*self.fs, path),
+            event,
+            env_vars,
+            current_dir,
+        )
+    }
+
+    fn run_scripts(
+        &mut self,
+        scripts: Vec<HookScript>,
+        event: &HookEvent,
+        env_vars: &HashMap<String, String>,
+        current_dir: Option<&Path>,
+    ) -> Result<(), WechselError> {
+        for script in scripts {
+            let settings = script.settings(&*self.fs);
+            let run = self.run(&script, &settings, event, env_vars, current_dir, false)?;
+            if !run.failed() {
+                continue;
+            }
+            if settings.continue_on_failure {
+                warn!("{:?} failed, continuing with the next hook", script.path);
+                continue;
+            }
+            return Err(WechselError::HookFailed {
+                hook: script.path,
+                code: run.exit_code,
+            });
+        }
+        Ok(())
+    }
+
+    /// Runs hooks at `path` and in `path.d` that can block a change, the stderr of a failing one
+    /// is part of the `Vetoed` error. They never run in the background.
+    pub fn run_veto_hooks(
+        &mut self,
+        path: &Path,
+        event: &HookEvent,
+        env_vars: &HashMap<String, String>,
+        current_dir: Option<&Path>,
+    ) -> Result<(), WechselError> {
+        self.run_veto_scripts(
+            hook_scripts_at(&*self.fs, path),
+            event,
+            env_vars,
+            current_dir,
+        )
+    }
+
+    fn run_veto_scripts(
+        &mut self,
+        scripts: Vec<HookScript>,
+        event: &HookEvent,
+        env_vars: &HashMap<String, String>,
+        current_dir: Option<&Path>,
+    ) -> Result<(), WechselError> {
+        for script in scripts {
+            let mut settings = script.settings(&*self.fs);
+            if settings.background {
+                warn!(
+                    "{:?} can block the change, so it doesn't run in the background",
+                    script.path
+                );
+                settings.background = false;
+            }
+            let run = self.run(&script, &settings, event, env_vars, current_dir, true)?;
+            if run.failed() && !settings.continue_on_failure {
+                return Err(WechselError::Vetoed {
+                    hook: script.path,
+                    stderr: run.stderr,
+                });
+            }
+            // Warnings of hooks that let the change through are still shown
+            if !run.stderr.is_empty() {
+                self.output
+                    .show(&script.path, HookOutput::Stderr, &run.stderr);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the pre-change hooks of the projects that are left, the first one that fails blocks the change
+    pub fn run_pre_change_hooks(
+        &mut self,
+        leaving: &[Rc<FoundProject>],
+        event: &HookEvent,
+        env_vars: &HashMap<String, String>,
+    ) -> Result<(), WechselError> {
+        for prj in leaving {
+            let scripts = self.allowed_scripts(prj, PRE_CHANGE_HOOK);
+            let event = project_event(prj, event);
+            let env_vars = project_env(prj, env_vars);
+            self.run_veto_scripts(scripts, &event, &env_vars, Some(&prj.path))?;
+        }
+        Ok(())
+    }
+
+    /// Runs the hook `name` of each of the projects in their folder. All of them run,
+    /// even if one fails, the first failure is returned.
+    pub fn run_project_hooks(
+        &mut self,
+        name: &str,
+        projects: &[Rc<FoundProject>],
+        event: &HookEvent,
+        env_vars: &HashMap<String, String>,
+    ) -> Result<(), WechselError> {
+        let mut result = Ok(());
+        for prj in projects {
+            let scripts = self.allowed_scripts(prj, name);
+            let event = project_event(prj, event);
+            let env_vars = project_env(prj, env_vars);
+            let hook_result = self.run_scripts(scripts, &event, &env_vars, Some(&prj.path));
+            result = result.and(hook_result);
+        }
+        result
+    }
+
+    // The scripts of the hook `name` of a project that were allowed and didn't change since,
+    // with the content that was checked
+    fn allowed_scripts(&self, prj: &Rc<FoundProject>, name: &str) -> Vec<HookScript> {
+        let path = PathBuf::from_iter([&prj.path, &PathBuf::from(name)]);
+        hook_scripts(&*self.fs, &path)
+            .into_iter()
+            .filter_map(|script| {
+                let content = self
+                    .fs
+                    .read(&script)
+                    .ok()
+                    .filter(|content| self.trust.allows(&script, content));
+                if content.is_none() {
+                    warn!(
+                        "Skipping {script:?}, it changed or was never allowed, `wechsel allow {}` allows it",
+                        prj.name
+                    );
+                }
+                Some(HookScript {
+                    content: Some(content?),
+                    path: script,
+                })
+            })
+            .collect()
+    }
+
+    // Runs a single script, `hold_stderr` keeps its stderr from the output sink
+    fn run(
+        &mut self,
+        hook: &HookScript,
+        settings: &HookSettings,
+        event: &HookEvent,
+        env_vars: &HashMap<String, String>,
+        current_dir: Option<&Path>,
+        hold_stderr: bool,
+    ) -> Result<HookRun, WechselError> {
+        let script = hook.path.as_path();
+        info!("Running {script:?}");
+        let start = Instant::now();
+        let timeout = settings.timeout.or(self.timeout);
+        let (mut command, _memory_file) = hook.command()?;
+        command.envs(env_vars);
+        if let Some(current_dir) = current_dir {
+            command.current_dir(current_dir);
+        }
+        let mut run = HookRun {
+            hook: script.to_path_buf(),
+            exit_code: None,
+            timed_out: false,
+            background: settings.background,
+            stdout: String::new(),
+            stderr: String::new(),
+        };
+
+        let input = match settings.json {
+            true => Some(serde_json::to_string(event)?),
+            false => None,
+        };
+        if settings.background {
+            self.spawn_background(command, script, input)?;
+            self.runs.push(run.clone());
+            return Ok(run);
+        }
+
+        // Outside of the group of the terminal a hook can't read from it, it would be stopped
+        let stdin = match input {
+            Some(_) => Stdio::piped(),
+            None => Stdio::null(),
+        };
+        let signals = SignalForwarding::install();
+        let mut child = command
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()?;
+        signals.forward_to(child.id());
+        write_input(&mut child, input);
+        let stdout = Arc::new(Mutex::new(String::new()));
+        let stderr = Arc::new(Mutex::new(String::new()));
+        let mut readers = vec![];
+        if let Some(pipe) = child.stdout.take() {
+            // The answer of a JSON hook is not meant for the user
+            let sink = (!settings.json).then(|| (self.output.clone(), HookOutput::Stdout));
+            readers.push(forward_output(
+                pipe,
+                script.to_path_buf(),
+                sink,
+                stdout.clone(),
+            ));
+        }
+        if let Some(pipe) = child.stderr.take() {
+            let sink = (!hold_stderr).then(|| (self.output.clone(), HookOutput::Stderr));
+            readers.push(forward_output(
+                pipe,
+                script.to_path_buf(),
+                sink,
+                stderr.clone(),
+            ));
+        }
+
+        let status = match timeout {
+            None => Some(child.wait()?),
+            Some(timeout) => loop {
+                if let Some(status) = child.try_wait()? {
+                    break Some(status);
+                }
+                if start.elapsed() >= timeout {
+                    // SAFETY: kill has no memory safety requirements, the group is the one of the hook
+                    unsafe { libc::kill(-(child.id() as i32), libc::SIGKILL) };
+                    let _ = child.wait();
+                    break None;
+                }
+                thread::sleep(Duration::from_millis(10));
+            },
+        };
+        // Processes the hook started and left running can hold the pipes open, they aren't waited for
+        let deadline = Instant::now() + OUTPUT_GRACE_PERIOD;
+        while readers.iter().any(|reader| !reader.is_finished()) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        drop(signals);
+
+        match status {
+            Some(status) => {
+                info!(
+                    "{script:?} finished after {:?} with {status}",
+                    start.elapsed()
+                );
+                run.exit_code = status.code();
+            }
+            None => {
+                warn!("{script:?} timed out after {:?}", start.elapsed());
+                run.timed_out = true;
+            }
+        }
+        run.stdout = stdout.lock().map(|c| c.clone()).unwrap_or_default();
+        run.stderr = stderr.lock().map(|c| c.clone()).unwrap_or_default();
+        if settings.json {
+            self.answer(script, &run.stdout);
+        }
+        self.runs.push(run.clone());
+        Ok(run)
+    }
+
+    // Takes over what a JSON hook asked for, hooks that print nothing ask for nothing
+    fn answer(&mut self, script: &Path, stdout: &str) {
+        if stdout.trim().is_empty() {
+            return;
+        }
+        let response: HookResponse = match serde_json::from_str(stdout) {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("{script:?} didn't answer with a valid JSON response: {e}");
+                return;
+            }
+        };
+        for (name, value) in response.env {
+            if !is_env_name(&name) {
+                warn!("{script:?} asked to export {name:?}, which is not a valid variable name");
+                continue;
+            }
+            self.env.insert(name, value);
+        }
+        for message in response.messages {
+            self.output.show(script, HookOutput::Message, &message);
+            self.messages.push(message);
+        }
+    }
+
+    // Detaches the hook into its own process group with its output going to the log file
+    fn spawn_background(
+        &self,
+        mut command: Command,
+        script: &Path,
+        input: Option<String>,
+    ) -> Result<(), WechselError> {
+        let log = self.log_file.as_ref().and_then(|log_file| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(log_file)
+                .ok()
+        });
+        let (stdout, stderr) = match log {
+            Some(log) => (Stdio::from(log.try_clone()?), Stdio::from(log)),
+            None => (Stdio::null(), Stdio::null()),
+        };
+        let stdin = match input {
+            Some(_) => Stdio::piped(),
+            None => Stdio::null(),
+        };
+        let mut child = command
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr)
+            .process_group(0)
+            .spawn()?;
+        write_input(&mut child, input);
+        info!("{script:?} runs in the background");
+        // Long running processes like the daemon would otherwise collect zombies
+        let script = script.to_path_buf();
+        thread::spawn(move || {
+            if let Ok(status) = child.wait() {
+                info!("{script:?} finished in the background with {status}");
+            }
+        });
+        Ok(())
+    }
+}
+
+/// The projects from the root project down to `prj`
+pub fn project_chain(prj: &Rc<FoundProject>) -> Vec<Rc<FoundProject>> {
+    let mut chain = vec![];
+    let mut prj = Some(prj.clone());
+    while let Some(p) = prj {
+        prj = p.parent.clone();
+        chain.push(p);
+    }
+    chain.reverse();
+    chain
+}
+
+/// The names of a chain like `home/uni/ml`
+pub fn chain_names(chain: &[Rc<FoundProject>]) -> String {
+    chain
+        .iter()
+        .map(|prj| prj.name.as_str())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The projects whose hooks run when changing from the end of `old` to the end of `new`
+pub struct Transition {
+    /// Innermost first
+    pub leaving: Vec<Rc<FoundProject>>,
+    /// Outermost first
+    pub entering: Vec<Rc<FoundProject>>,
+}
+
+impl Transition {
+    /// The projects both chains share are neither left nor entered
+    pub fn new(old: &[Rc<FoundProject>], new: &[Rc<FoundProject>]) -> Transition {
+        let shared = old
+            .iter()
+            .zip(new.iter())
+            .take_while(|(old, new)| old.path == new.path)
+            .count();
+        Transition {
+            leaving: old[shared..].iter().rev().cloned().collect(),
+            entering: new[shared..].to_vec(),
+        }
+    }
+}
+
+// Writes on another thread, a hook that doesn't read its input can't block wechsel
+fn write_input(child: &mut Child, input: Option<String>) {
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+        thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        });
+    }
+}
+
+/// Names that can be exported in bash and fish
+pub fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// The event of a hook of a single project
+fn project_event(prj: &Rc<FoundProject>, event: &HookEvent) -> HookEvent {
+    HookEvent {
+        hook_project: Some(prj.into()),
+        ..event.clone()
+    }
+}
+
+// The variables of a hook of a single project
+fn project_env(
+    prj: &Rc<FoundProject>,
+    env_vars: &HashMap<String, String>,
+) -> HashMap<String, String> {
+    let mut env_vars = env_vars.clone();
+    env_vars.insert("HOOK_PRJ".to_owned(), prj.name.clone());
+    env_vars.insert(
+        "HOOK_PRJ_PATH".to_owned(),
+        prj.path.to_str().unwrap_or_default().to_owned(),
+    );
+    env_vars
+}
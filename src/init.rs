@@ -6,21 +6,26 @@ use std::{
 
 use dialoguer::MultiSelect;
 use io::Write;
+use log::{info, warn};
 use std::os::unix::fs::PermissionsExt;
 
-use crate::utils::{
-    DEFAULT_ROOT_PRJ, PROJECT_EXTENSION, WECHSEL_FOLDER_EXTENSION, bashrc_path, fish_config_path,
-    get_home_folder_paths, on_prj_change_path, path_from_iter,
+use crate::output::say;
+use crate::upgrade::{LAYOUT_VERSION, write_layout_version};
+use wechsel::{
+    PROJECT_EXTENSION, ProjectLock, Shell, WECHSEL_FOLDER_EXTENSION, WechselError, bashrc_path,
+    fish_config_path, home_dir, home_folder_paths, load_config, path_from_iter,
 };
 
-pub fn init_prj(config_dir: PathBuf, no_prompts: bool) -> String {
-    println!("Initializing Wechsel");
-    let home = dirs::home_dir().expect("Could not find home directory");
+pub fn init_prj(config_dir: PathBuf, no_prompts: bool) -> Result<String, WechselError> {
+    let _lock = ProjectLock::acquire(&config_dir)?;
+    say!("Initializing Wechsel");
+    let config = load_config(&config_dir)?;
+    let home = home_dir().ok_or(io::Error::other("No Home dir found"))?;
 
     // Check for an existing installation
     let mut found_project_folder = None;
     let mut single_project_exception = true;
-    for entry in fs::read_dir(&home).unwrap().filter_map(|entry| entry.ok()) {
+    for entry in fs::read_dir(&home)?.filter_map(|entry| entry.ok()) {
         if entry.file_type().map(|typ| typ.is_dir()).unwrap_or(false) {
             let name = entry.file_name();
             let name = name.as_os_str().to_str();
@@ -48,18 +53,24 @@ pub fn init_prj(config_dir: PathBuf, no_prompts: bool) -> String {
     let prj_path = match (found_project_folder, single_project_exception) {
         (Some(path), true) => path, // The single project exception means that the root project is the sole project in the home folder and home folder has no .w folders of its own
         (Some(_), false) => home.clone(),
-        _ => path_from_iter([&home, &PathBuf::from(DEFAULT_ROOT_PRJ)])
+        _ => path_from_iter([&home, &PathBuf::from(&config.general.root_project)])
             .with_extension(PROJECT_EXTENSION),
     };
 
     if !prj_path.exists() {
-        println!("Creating root project folder: at {prj_path:?}");
-        fs::create_dir_all(&prj_path).expect("Could not create project folder");
+        say!("Creating root project folder: at {prj_path:?}");
+        fs::create_dir_all(&prj_path)?;
+        info!("Created root project folder {prj_path:?}");
+        // A new setup starts out with the current layout
+        write_layout_version(LAYOUT_VERSION, &config_dir)?;
     } else {
-        println!("root project folder already exists");
+        say!("root project folder already exists");
     }
 
-    let (home_folder_names, home_folder_paths): (Vec<_>, Vec<_>) = get_home_folder_paths().unzip();
+    let (home_folder_names, home_folder_paths): (Vec<_>, Vec<_>) =
+        home_folder_paths(&config.general.home_folders)
+            .into_iter()
+            .unzip();
 
     let folders = if !no_prompts {
         println!();
@@ -67,16 +78,17 @@ pub fn init_prj(config_dir: PathBuf, no_prompts: bool) -> String {
         println!("Wechsel will now move some of your user folders to the root project.");
         println!("You should select all folders that you want projects to be able to use.");
 
-        let Ok(folders) = MultiSelect::new()
+        MultiSelect::new()
             .with_prompt("Select folders to move to the root project")
             .items(&home_folder_names)
             .report(false)
             .interact()
-            .map(|i| i.into_iter().map(|i| home_folder_names[i]).collect())
-        else {
-            std::process::exit(1);
-        };
-        folders
+            .map(|i| {
+                i.into_iter()
+                    .map(|i| home_folder_names[i].clone())
+                    .collect()
+            })
+            .map_err(|_| WechselError::Cancelled)?
     } else {
         home_folder_names.clone()
     };
@@ -95,18 +107,21 @@ pub fn init_prj(config_dir: PathBuf, no_prompts: bool) -> String {
             .with_extension(WECHSEL_FOLDER_EXTENSION);
 
         if !folder_path.is_dir() {
-            println!("Folder {folder:?} does not exist or isn't a directory");
+            say!("Folder {folder:?} does not exist or isn't a directory");
             continue;
         }
 
         if target.exists() {
-            println!("Folder {folder:?} already exists in root project");
+            say!("Folder {folder:?} already exists in root project");
             continue;
         }
 
-        println!("Moving folder {:?} to {:?}", folder_path, &target);
-        if let Err(err) = fs::rename(folder_path, &target) {
-            eprintln!("Could not move folder: {folder_path:?} to {target:?}, ignoring it; {err}");
+        say!("Moving folder {:?} to {:?}", folder_path, &target);
+        match fs::rename(folder_path, &target) {
+            Ok(()) => info!("Moved {folder_path:?} to {target:?}"),
+            Err(err) => {
+                warn!("Could not move folder: {folder_path:?} to {target:?}, ignoring it; {err}")
+            }
         }
     }
 
@@ -115,22 +130,19 @@ pub fn init_prj(config_dir: PathBuf, no_prompts: bool) -> String {
         println!();
         println!("Would you like to integrate Wechsel into your shells?");
 
-        let items = vec!["Bash", "Fish"];
-        let Ok(shells) = MultiSelect::new()
+        let items = &config.general.shells;
+        MultiSelect::new()
             .with_prompt("Select shells")
-            .items(&items)
+            .items(items)
             .report(false)
             .interact()
             .map(|i| i.into_iter().map(|i| items[i]).collect())
-        else {
-            std::process::exit(1);
-        };
-        shells
+            .map_err(|_| WechselError::Cancelled)?
     } else {
-        vec!["Bash", "Fish"]
+        config.general.shells.clone()
     };
 
-    if shells.contains(&"Bash") {
+    if shells.contains(&Shell::Bash) {
         let mut file = OpenOptions::new().append(true).open(bashrc_path());
 
         if let Ok(file) = &mut file {
@@ -138,15 +150,16 @@ pub fn init_prj(config_dir: PathBuf, no_prompts: bool) -> String {
 
             for line in bash.lines() {
                 if let Err(e) = writeln!(file, "{line}") {
-                    eprintln!("Couldn't write to file: {e}");
+                    warn!("Couldn't write to file: {e}");
                 }
             }
+            info!("Added the shell integration to {:?}", bashrc_path());
         } else {
-            eprintln!("Couldn't open .bashrc, continuing without modifying it.");
+            warn!("Couldn't open .bashrc, continuing without modifying it.");
         }
     }
 
-    if shells.contains(&"Fish") {
+    if shells.contains(&Shell::Fish) {
         let mut file = OpenOptions::new()
             .append(true)
             .open(fish_config_path(&config_dir));
@@ -155,32 +168,35 @@ pub fn init_prj(config_dir: PathBuf, no_prompts: bool) -> String {
             let fish = include_str!("../config_files/default_fish_config");
             for line in fish.lines() {
                 if let Err(e) = writeln!(file, "{line}") {
-                    eprintln!("Couldn't write to file: {e}");
+                    warn!("Couldn't write to file: {e}");
                 }
             }
+            info!(
+                "Added the shell integration to {:?}",
+                fish_config_path(&config_dir)
+            );
         } else {
-            eprintln!("Couldn't open fish config, continuing without modifying it.");
+            warn!("Couldn't open fish config, continuing without modifying it.");
         }
     }
 
-    let on_prj_change = on_prj_change_path(&config_dir);
+    let on_prj_change = config.hooks.on_prj_change_script(&config_dir);
 
     if !on_prj_change.exists() {
-        println!("Creating on-prj-change script");
+        say!("Creating on-prj-change script");
 
         let default = include_str!("../config_files/default_on_prj_change");
 
-        fs::write(&on_prj_change, default).expect("Could not create on-prj-change script");
+        fs::write(&on_prj_change, default)?;
 
-        let mut permissions = fs::metadata(&on_prj_change)
-            .expect("Could not get metadata")
-            .permissions();
+        let mut permissions = fs::metadata(&on_prj_change)?.permissions();
 
         // Add execute permission
         permissions.set_mode(permissions.mode() | 0b001001001);
-        fs::set_permissions(&on_prj_change, permissions).expect("Could not set permissions");
+        fs::set_permissions(&on_prj_change, permissions)?;
+        info!("Created {on_prj_change:?}");
     } else {
-        println!("on-prj-change folder already exists");
+        say!("on-prj-change folder already exists");
     }
-    DEFAULT_ROOT_PRJ.to_string()
+    Ok(config.general.root_project)
 }
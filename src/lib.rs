@@ -1,5 +1,27 @@
+mod config;
+mod conflict;
+mod error;
+mod file_system;
+mod history;
+mod hooks;
+mod lock;
+mod old_config;
+mod time_log;
 mod tree;
+mod trust;
 mod utils;
+mod workspace;
 
+pub use config::*;
+pub use conflict::*;
+pub use error::*;
+pub use file_system::*;
+pub use history::*;
+pub use hooks::*;
+pub use lock::*;
+pub use old_config::*;
+pub use time_log::*;
 pub use tree::*;
+pub use trust::*;
 pub use utils::*;
+pub use workspace::*;
@@ -0,0 +1,61 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+
+use crate::error::WechselError;
+use crate::utils::lock_path;
+
+/// Held while wechsel changes the links in the home folder, so two processes can't interleave.
+/// The lock is released on drop, locks of processes that no longer run are taken over.
+/// Acquiring it again in the process that holds it succeeds, the outermost lock releases it.
+pub struct ProjectLock {
+    path: Option<PathBuf>,
+}
+
+impl ProjectLock {
+    pub fn acquire(config_dir: &PathBuf) -> Result<ProjectLock, WechselError> {
+        let path = lock_path(config_dir);
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    write!(file, "{}", std::process::id())?;
+                    debug!("Acquired {path:?}");
+                    return Ok(ProjectLock { path: Some(path) });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    let pid = fs::read_to_string(&path)
+                        .ok()
+                        .and_then(|pid| pid.trim().parse::<u32>().ok());
+                    match pid {
+                        Some(pid) if pid == std::process::id() => {
+                            return Ok(ProjectLock { path: None });
+                        }
+                        Some(pid) if Path::new(&format!("/proc/{pid}")).exists() => {
+                            return Err(WechselError::LockHeld(pid));
+                        }
+                        // Stale lock
+                        _ => {
+                            debug!("Removing the stale lock {path:?}");
+                            fs::remove_file(&path)?
+                        }
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for ProjectLock {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            if fs::remove_file(path).is_ok() {
+                debug!("Released {path:?}");
+            }
+        }
+    }
+}
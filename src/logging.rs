@@ -0,0 +1,111 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use chrono::Local;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use wechsel::{get_state_dir, log_file_path};
+
+// The log file is rotated once it grows beyond this size
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+// Number of rotated log files that are kept next to the current one
+const KEPT_LOGS: usize = 3;
+
+/// Writes records to stderr depending on the verbosity and everything down to debug to the log file
+struct Logger {
+    stderr_level: LevelFilter,
+    file: Mutex<Option<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Only wechsel's own records, not the ones of dependencies
+        metadata.target().starts_with("wechsel")
+            && metadata.level() <= self.stderr_level.max(LevelFilter::Debug)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if record.level() <= self.stderr_level {
+            match record.level() {
+                Level::Error | Level::Warn => eprintln!("{}", record.args()),
+                level => eprintln!("[{}] {}", level.as_str().to_lowercase(), record.args()),
+            }
+        }
+        if record.level() <= LevelFilter::Debug {
+            if let Ok(mut file) = self.file.lock() {
+                if let Some(file) = file.as_mut() {
+                    let _ = writeln!(
+                        file,
+                        "{} {:<5} [{}] {}",
+                        Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                        record.level(),
+                        std::process::id(),
+                        record.args()
+                    );
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            if let Some(file) = file.as_mut() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Sets up logging, `verbose` is the number of `-v` flags
+pub fn init_logging(verbose: u8, quiet: bool) {
+    let stderr_level = match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Warn,
+        (false, 1) => LevelFilter::Info,
+        (false, 2) => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+
+    let logger = Logger {
+        stderr_level,
+        file: Mutex::new(open_log_file()),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(stderr_level.max(LevelFilter::Debug));
+    }
+}
+
+fn open_log_file() -> Option<File> {
+    let state_dir = get_state_dir()?;
+    fs::create_dir_all(&state_dir).ok()?;
+    let path = log_file_path(&state_dir);
+
+    if fs::metadata(&path)
+        .map(|meta| meta.len() > MAX_LOG_SIZE)
+        .unwrap_or_default()
+    {
+        rotate(&path);
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .ok()
+}
+
+// wechsel.log -> wechsel.log.1 -> wechsel.log.2 ..., the oldest one is dropped
+fn rotate(path: &PathBuf) {
+    let rotated = |idx: usize| PathBuf::from(format!("{}.{idx}", path.to_string_lossy()));
+    for idx in (1..KEPT_LOGS).rev() {
+        let _ = fs::rename(rotated(idx), rotated(idx + 1));
+    }
+    let _ = fs::rename(path, rotated(1));
+}
@@ -1,15 +1,38 @@
-use crate::utils::{get_config_dir, query_active_project};
-use crate::{change::change_prj, new::new_prj_cmd};
+use crate::auto::{auto_once, daemon};
+use crate::logging::init_logging;
+use crate::output::{hook_output, is_json, say, set_json, set_quiet};
+use crate::report::{GroupBy, ReportFormat, ReportOptions, report};
+use crate::{
+    cd_hook::{cd_hook, clear_cd_hook_cache},
+    change::switch_project,
+    new::new_prj_cmd,
+    resolve::{back_target, down_target, forward_target, project_at, resolve_project, up_target},
+};
+use chrono::{Local, TimeZone};
 use clap::{Parser, Subcommand};
 use init::init_prj;
-use std::fs;
-use tree::{TreeOutput, get_project_tree};
+use std::{collections::BTreeMap, fs, path::PathBuf, rc::Rc};
+use wechsel::{
+    CONFIG_DIR_ENV, ChangeOptions, ConflictStrategy, FoundProject, HOME_ENV, History, Navigation,
+    OsFileSystem, SwitchEntry, TimeEvent, TreeOutput, WechselError, Workspace, append_time_event,
+    config_get, config_set, config_values, now, pause, tree_schema,
+};
 
+mod auto;
+mod cd_hook;
 mod change;
+#[cfg(feature = "dbus")]
+mod dbus;
+mod doctor;
 mod init;
+mod logging;
+mod migrate;
 mod new;
-mod tree;
-mod utils;
+mod output;
+mod report;
+mod resolve;
+mod upgrade;
+mod watch;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -18,13 +41,70 @@ pub struct Args {
     command: Option<Command>,
 
     project_name: Option<String>,
+
+    #[clap(
+        long,
+        global = true,
+        help = "print the result or the error as a json object, see the README for the exit codes"
+    )]
+    json: bool,
+
+    #[clap(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        help = "print more details, repeat for even more"
+    )]
+    verbose: u8,
+
+    #[clap(
+        short,
+        long,
+        global = true,
+        conflicts_with = "verbose",
+        help = "only print errors"
+    )]
+    quiet: bool,
+
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        value_name = "STRATEGY",
+        help = "what to do with real folders in the place of a link, overrides the config file"
+    )]
+    on_conflict: Option<ConflictStrategy>,
+
+    #[clap(
+        long,
+        global = true,
+        help = "change the project even if a pre-change hook objects"
+    )]
+    force: bool,
+
+    #[clap(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "use DIR instead of the home folder, like setting WECHSEL_HOME"
+    )]
+    home: Option<PathBuf>,
+
+    #[clap(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "use DIR as the config dir, like setting WECHSEL_CONFIG_DIR"
+    )]
+    config_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
     #[clap(about = "[Default] Change the active project.")]
     Change {
-        /// project to change to
+        /// project to change to, `.` or a path for the project containing it, `-` for the previous project
         project_name: String,
     },
     #[clap(about = "Turn the working directory into a new project.")]
@@ -32,11 +112,31 @@ pub enum Command {
         /// Name of the new project
         project_name: String,
 
+        /// parent project, also accepts `.`, a path or `-`
         #[clap(short, long)]
         parent: Option<String>,
         #[clap(long, value_parser, num_args = 1.., value_delimiter = ' ', help="the list of folders to create in the new project")]
         folders: Option<Vec<String>>,
     },
+    #[clap(about = "Rename a project, the links of the active project follow it.")]
+    Rename {
+        /// project to rename, also accepts `.`, a path or `-`
+        project_name: String,
+        /// the new name
+        new_name: String,
+    },
+    #[clap(
+        about = "Allow the hooks and init scripts in the folder of a project to run as they are now."
+    )]
+    Allow {
+        /// project whose scripts to allow, the active project if not given, also accepts `.`, a path or `-`
+        project_name: Option<String>,
+    },
+    #[clap(about = "Stop the hooks and init scripts in the folder of a project from running.")]
+    Deny {
+        /// project whose scripts to deny, the active project if not given, also accepts `.`, a path or `-`
+        project_name: Option<String>,
+    },
     #[clap(
         about = "Initialize the config file and create a default project and move the folders to it."
     )]
@@ -45,27 +145,166 @@ pub enum Command {
         yes: bool,
     },
 
+    #[clap(about = "Move a setup of wechsel <= 0.2.3 to the current folder layout.")]
+    Migrate {
+        #[clap(short, long, help = "don't ask before migrating")]
+        yes: bool,
+        #[clap(long, help = "only print what would be done")]
+        dry_run: bool,
+    },
+
+    #[clap(about = "Upgrade a setup made by an older version of wechsel.")]
+    Upgrade {
+        #[clap(short, long, help = "don't ask before upgrading")]
+        yes: bool,
+    },
+
+    #[clap(about = "Show or change the settings in config.toml.")]
+    Config {
+        #[clap(subcommand)]
+        action: ConfigAction,
+    },
+
     #[clap(about = "Returns the project tree structure as a json string")]
     Tree {
         #[clap(long, help = "return the list of wechsel folders per project")]
         folders: bool,
+        #[clap(long, help = "print the JSON Schema of the output instead")]
+        schema: bool,
+    },
+
+    #[clap(about = "Change to the project that was active before, repeatable through the history.")]
+    Back,
+    #[clap(about = "Undo a `wechsel back`.")]
+    Forward,
+    #[clap(about = "Change to the parent of the active project.")]
+    Up,
+    #[clap(about = "Change to a child of the active project.")]
+    Down,
+    #[clap(about = "List the recent project changes.")]
+    History {
+        #[clap(
+            short = 'n',
+            long,
+            default_value_t = 20,
+            help = "number of entries to show"
+        )]
+        count: usize,
+    },
+
+    #[clap(about = "Pause the time tracking, until the next project change or `wechsel resume`.")]
+    Pause {
+        #[clap(long, help = "start of the pause, defaults to now")]
+        from: Option<String>,
+        #[clap(long, help = "end of the pause, for marking past idle periods")]
+        to: Option<String>,
+    },
+    #[clap(about = "Resume the time tracking for the active project.")]
+    Resume,
+    #[clap(about = "Sum up the time spent in each project.")]
+    Report {
+        #[clap(
+            long,
+            help = "YYYY-MM-DD, \"YYYY-MM-DD HH:MM\", HH:MM, today or yesterday"
+        )]
+        since: Option<String>,
+        #[clap(
+            long,
+            help = "YYYY-MM-DD, \"YYYY-MM-DD HH:MM\", HH:MM, today or yesterday"
+        )]
+        until: Option<String>,
+        #[clap(long, value_enum, default_value_t = GroupBy::Project)]
+        by: GroupBy,
+        #[clap(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+        #[clap(
+            long,
+            value_name = "DEPTH",
+            help = "count projects deeper than DEPTH towards their ancestor at DEPTH, the root project has depth 0"
+        )]
+        rollup: Option<usize>,
+    },
+
+    #[clap(about = "Change to the project selected by the rules in the config file.")]
+    Auto,
+    #[clap(
+        about = "Keep running and apply the rules in the config file whenever their result changes."
+    )]
+    Daemon,
+
+    #[cfg(feature = "dbus")]
+    #[clap(about = "Provide the org.wechsel.Manager service on the D-Bus session bus.")]
+    Service,
+
+    #[clap(
+        about = "Print an event for every change of the project tree or the active project, with --json one object per line."
+    )]
+    Watch,
+
+    #[clap(about = "Check the wechsel setup for problems.")]
+    Doctor {
+        #[clap(long, help = "repair the problems that can be repaired safely")]
+        fix: bool,
+    },
+
+    #[clap(
+        about = "Used by the shell integration, switch to the project containing the given directory."
+    )]
+    CdHook {
+        /// the directory that was changed into
+        path: PathBuf,
+
+        #[clap(long, help = "ask before switching")]
+        prompt: bool,
     },
 }
 
-pub fn main_with_args(args: Args) {
-    let config_dir = get_config_dir().expect("No config folder found");
+#[derive(Debug, Subcommand)]
+pub enum ConfigAction {
+    #[clap(about = "Print the value of a setting, like general.root_project.")]
+    Get { key: String },
+    #[clap(about = "Change a setting, values are parsed as toml, like '[\"Desktop\", \"Music\"]'.")]
+    Set { key: String, value: String },
+    #[clap(about = "Print all settings with their values.")]
+    List,
+}
+
+pub fn main_with_args(args: Args) -> Result<Option<serde_json::Value>, WechselError> {
+    let workspace = Workspace::from_env()?.with_hook_output(hook_output());
+    let config_dir = workspace.config_dir().clone();
     if !config_dir.exists() {
-        fs::create_dir_all(&config_dir).expect("Could not create config folder");
+        fs::create_dir_all(&config_dir)?;
     }
 
     if args.project_name.is_none() && args.command.is_none() {
-        eprintln!(
+        return Err(WechselError::Invalid(
             "Either specify a command or a target project. Call wechsel --help for more information"
-        );
-        std::process::exit(1);
+                .to_string(),
+        ));
+    }
+
+    // These either create or repair the setup themselves
+    let skip_layout_check = matches!(
+        args.command,
+        Some(
+            Command::Init { .. }
+                | Command::Migrate { .. }
+                | Command::Upgrade { .. }
+                | Command::Doctor { .. }
+                | Command::Config { .. }
+                | Command::CdHook { .. }
+        )
+    );
+    if !skip_layout_check {
+        upgrade::check_layout(&config_dir)?;
     }
 
-    let mut prj_name = args.project_name;
+    let mut navigation = Navigation::Switch;
+    let mut target = args
+        .project_name
+        .map(|prj_name| resolve_project(&prj_name, &workspace))
+        .transpose()?;
+    let mut result = None;
 
     if let Some(cmd) = args.command {
         match cmd {
@@ -74,38 +313,241 @@ pub fn main_with_args(args: Args) {
                 parent,
                 folders,
             } => {
-                prj_name = Some(project_name.clone());
-                new_prj_cmd(parent, folders, &project_name, &config_dir);
-            }
-            Command::Change { project_name } => prj_name = Some(project_name.clone()),
-            Command::Tree { folders } => {
-                println!(
-                    "{}",
-                    serde_json::to_string_pretty(&TreeOutput {
-                        tree: get_project_tree(&config_dir, folders),
-                        active: query_active_project().unwrap_or_default(),
-                    })
-                    .unwrap_or_default()
-                )
-            }
-            Command::Init { yes } => prj_name = Some(init_prj(config_dir.clone(), yes)),
+                let parent = parent
+                    .map(|parent| resolve_project(&parent, &workspace))
+                    .transpose()?;
+                let path = new_prj_cmd(parent, folders, &project_name, &config_dir)?;
+                clear_cd_hook_cache(&config_dir);
+                target = Some(project_at(&path, &workspace)?);
+            }
+            Command::Change { project_name } => {
+                target = Some(resolve_project(&project_name, &workspace)?)
+            }
+            Command::Tree { schema: true, .. } => {
+                let schema = tree_schema();
+                if is_json() {
+                    result = Some(schema);
+                } else {
+                    println!("{}", serde_json::to_string_pretty(&schema)?)
+                }
+            }
+            Command::Tree { folders, .. } => {
+                let tree = TreeOutput::new(
+                    workspace.tree(folders)?,
+                    workspace.active().unwrap_or_default(),
+                );
+                if is_json() {
+                    result = Some(serde_json::to_value(tree)?);
+                } else {
+                    println!("{}", serde_json::to_string_pretty(&tree)?)
+                }
+            }
+            Command::Rename {
+                project_name,
+                new_name,
+            } => {
+                let prj = resolve_project(&project_name, &workspace)?;
+                let renamed = workspace.rename_at(&prj.path, &new_name);
+                // A failing on-prj-rename hook doesn't undo the rename itself
+                if let Ok(_) | Err(WechselError::HookFailed { .. }) = renamed {
+                    clear_cd_hook_cache(&config_dir);
+                    say!("Renamed Project {} to {new_name}", prj.name);
+                }
+                result = Some(serde_json::to_value(renamed?)?);
+            }
+            Command::Allow { project_name } => {
+                let prj = project_or_active(project_name, &workspace)?;
+                let allowed = workspace.allow_at(&prj.path)?;
+                for script in allowed.iter() {
+                    say!("Allowed {}", script.script.display());
+                }
+                if allowed.is_empty() {
+                    say!("Project {} has no scripts to allow", prj.name);
+                }
+                result = Some(serde_json::to_value(allowed)?);
+            }
+            Command::Deny { project_name } => {
+                let prj = project_or_active(project_name, &workspace)?;
+                let denied = workspace.deny_at(&prj.path)?;
+                for script in denied.iter() {
+                    say!("Denied {}", script.display());
+                }
+                if denied.is_empty() {
+                    say!("Project {} had no allowed scripts", prj.name);
+                }
+                result = Some(serde_json::to_value(denied)?);
+            }
+            Command::Init { yes } => {
+                target = Some(workspace.find(&init_prj(config_dir.clone(), yes)?)?)
+            }
+            Command::Migrate { yes, dry_run } => {
+                let plan = migrate::migrate(&config_dir, yes, dry_run)?;
+                result = Some(serde_json::to_value(&plan)?);
+                if !dry_run {
+                    clear_cd_hook_cache(&config_dir);
+                    let active = migrate::migrated_project(plan, &config_dir)?;
+                    target = Some(workspace.find(&active)?);
+                }
+            }
+            Command::Config { action } => result = Some(config_cmd(action, &config_dir)?),
+            Command::Upgrade { yes } => {
+                result = Some(serde_json::to_value(upgrade::upgrade(&config_dir, yes)?)?)
+            }
+            Command::CdHook { path, prompt } => {
+                target = cd_hook(&path, prompt, &workspace)
+                    .and_then(|path| workspace.find_by_path(&path).ok().flatten())
+            }
+            Command::Back => {
+                navigation = Navigation::Back;
+                target = Some(back_target(&workspace)?);
+            }
+            Command::Forward => {
+                navigation = Navigation::Forward;
+                target = Some(forward_target(&workspace)?);
+            }
+            Command::Up => target = Some(up_target(&workspace)?),
+            Command::Down => target = Some(down_target(&workspace)?),
+            Command::History { count } => {
+                result = Some(serde_json::to_value(print_history(count, &config_dir))?)
+            }
+            Command::Pause { from, to } => pause(from, to)?,
+            Command::Resume => append_time_event(&OsFileSystem, TimeEvent::Resume, now())?,
+            #[cfg(feature = "dbus")]
+            Command::Service => dbus::serve(&config_dir)
+                .map_err(|e| std::io::Error::other(format!("D-Bus service failed: {e}")))?,
+            Command::Watch => watch::watch(&config_dir)?,
+            Command::Doctor { fix } => {
+                result = Some(serde_json::to_value(doctor::doctor(fix, &config_dir)?)?)
+            }
+            Command::Auto => result = Some(serde_json::to_value(auto_once(&config_dir)?)?),
+            Command::Daemon => daemon(&config_dir)?,
+            Command::Report {
+                since,
+                until,
+                by,
+                format,
+                rollup,
+            } => {
+                let rows = report(
+                    ReportOptions {
+                        since,
+                        until,
+                        by,
+                        format,
+                        rollup,
+                    },
+                    &config_dir,
+                )?;
+                result = Some(serde_json::to_value(rows)?);
+            }
         }
     }
 
-    if let Some(prj_name) = prj_name {
-        //Change Project
-        match change_prj(&prj_name, config_dir) {
-            Ok(_) => {
-                println!("Changed to Project {prj_name}");
+    if let Some(prj) = target {
+        let options = ChangeOptions {
+            conflict: args.on_conflict,
+            force: args.force,
+        };
+        let change = switch_project(&prj, &config_dir, navigation, false, options)?;
+        result = Some(serde_json::to_value(change)?);
+    }
+    Ok(result)
+}
+
+// The given project or the active one
+fn project_or_active(
+    project_name: Option<String>,
+    workspace: &Workspace,
+) -> Result<Rc<FoundProject>, WechselError> {
+    match project_name {
+        Some(project_name) => resolve_project(&project_name, workspace),
+        None => workspace.active_project().ok_or(WechselError::NotFound(
+            "There is no active project".to_string(),
+        )),
+    }
+}
+
+/// Prints the last `count` switches and returns them for the json output
+fn print_history(count: usize, config_dir: &PathBuf) -> Vec<SwitchEntry> {
+    let mut history = History::load(&OsFileSystem, config_dir);
+    let start = history.switches.len().saturating_sub(count);
+    for entry in &history.switches[start..] {
+        let time = Local
+            .timestamp_opt(entry.time, 0)
+            .single()
+            .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        say!("{time}  {} -> {}", entry.from, entry.to);
+    }
+    history.switches.split_off(start)
+}
+
+fn display_setting(value: Option<&toml::Value>) -> String {
+    value
+        .map(|value| value.to_string())
+        .unwrap_or("(not set)".to_string())
+}
+
+fn config_cmd(
+    action: ConfigAction,
+    config_dir: &PathBuf,
+) -> Result<serde_json::Value, WechselError> {
+    match action {
+        ConfigAction::Get { key } => {
+            let value = config_get(&key, config_dir)?;
+            match &value {
+                Some(toml::Value::String(value)) => say!("{value}"),
+                Some(value) => say!("{value}"),
+                // Nothing to print for a setting that is not set
+                None => {}
             }
-            Err(e) => {
-                println!("Could not change to Project {prj_name}, Error: {e}");
+            Ok(serde_json::to_value(value)?)
+        }
+        ConfigAction::Set { key, value } => {
+            config_set(&key, &value, config_dir)?;
+            let value = config_get(&key, config_dir)?;
+            say!("{key} = {}", display_setting(value.as_ref()));
+            Ok(serde_json::to_value(value)?)
+        }
+        ConfigAction::List => {
+            let values = config_values(config_dir)?;
+            for (key, value) in values.iter() {
+                say!("{key} = {}", display_setting(value.as_ref()));
             }
+            Ok(serde_json::to_value(
+                values.into_iter().collect::<BTreeMap<_, _>>(),
+            )?)
         }
     }
 }
+
 fn main() {
     let args = Args::parse();
+    // Through the environment the scripts and the wechsel processes they start use them as well,
+    // absolute because the scripts don't run in the same working directory
+    let cwd = std::env::current_dir().unwrap_or_default();
+    if let Some(home) = &args.home {
+        std::env::set_var(HOME_ENV, cwd.join(home));
+    }
+    if let Some(config_dir) = &args.config_dir {
+        std::env::set_var(CONFIG_DIR_ENV, cwd.join(config_dir));
+    }
+    set_json(args.json);
+    set_quiet(args.quiet);
+    init_logging(args.verbose, args.quiet);
 
-    main_with_args(args);
+    let result = main_with_args(args);
+    if is_json() {
+        let output = match &result {
+            Ok(result) => serde_json::json!({ "ok": true, "result": result }),
+            Err(e) => serde_json::json!({ "ok": false, "error": e }),
+        };
+        println!("{output}");
+    }
+    if let Err(e) = result {
+        if !is_json() {
+            eprintln!("{e}");
+        }
+        std::process::exit(e.exit_code());
+    }
 }
@@ -0,0 +1,279 @@
+use std::{
+    cmp::Reverse,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use dialoguer::Confirm;
+use log::{debug, info, warn};
+use serde::Serialize;
+
+use crate::output::say;
+use wechsel::{
+    OldConfig, OldProject, OsFileSystem, PROJECT_EXTENSION, ProjectLock, WECHSEL_FOLDER_EXTENSION,
+    WechselError, find_project, free_path, get_old_config_file_path,
+    get_old_config_file_path_unchecked, get_project_tree, home_dir, path_from_iter,
+};
+
+/// A single filesystem operation of a migration, all of them are planned before any is run
+#[derive(Debug, Serialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum MigrationStep {
+    CreateDir {
+        path: PathBuf,
+    },
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Moves everything left in `from` into `to`, except for the folders leading to `to`
+    MoveContents {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    RemoveIfEmpty {
+        path: PathBuf,
+    },
+    RemoveLink {
+        path: PathBuf,
+    },
+    Backup {
+        from: PathBuf,
+        to: PathBuf,
+    },
+}
+
+impl fmt::Display for MigrationStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationStep::CreateDir { path } => write!(f, "Create {path:?}"),
+            MigrationStep::Move { from, to } => write!(f, "Move {from:?} to {to:?}"),
+            MigrationStep::MoveContents { from, to } => {
+                write!(f, "Move the contents of {from:?} to {to:?}")
+            }
+            MigrationStep::RemoveIfEmpty { path } => write!(f, "Remove {path:?} if it is empty"),
+            MigrationStep::RemoveLink { path } => write!(f, "Remove the link {path:?}"),
+            MigrationStep::Backup { from, to } => write!(f, "Keep {from:?} as {to:?}"),
+        }
+    }
+}
+
+impl MigrationStep {
+    fn run(&self) -> io::Result<()> {
+        match self {
+            MigrationStep::CreateDir { path } => fs::create_dir_all(path)?,
+            MigrationStep::Move { from, to } | MigrationStep::Backup { from, to } => {
+                fs::rename(from, to)?
+            }
+            MigrationStep::MoveContents { from, to } => {
+                for entry in fs::read_dir(from)? {
+                    let entry = entry?;
+                    if to.starts_with(entry.path()) {
+                        continue;
+                    }
+                    let destination = free_path(&OsFileSystem, to, Path::new(&entry.file_name()));
+                    fs::rename(entry.path(), &destination)?;
+                    debug!("Moved {:?} to {destination:?}", entry.path());
+                }
+            }
+            MigrationStep::RemoveIfEmpty { path } => {
+                if let Err(e) = fs::remove_dir(path) {
+                    debug!("Kept {path:?}: {e}");
+                    return Ok(());
+                }
+            }
+            MigrationStep::RemoveLink { path } => fs::remove_file(path)?,
+        }
+        info!("{self}");
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+pub struct MigrationPlan {
+    pub steps: Vec<MigrationStep>,
+    /// The project that was active in the old setup
+    pub active: String,
+}
+
+/// Moves a setup of wechsel <= 0.2.3 to the `.p`/`.w` layout, `dry_run` only returns the plan
+pub fn migrate(
+    config_dir: &PathBuf,
+    no_prompts: bool,
+    dry_run: bool,
+) -> Result<MigrationPlan, WechselError> {
+    let old_config_path =
+        get_old_config_file_path(config_dir).ok_or(WechselError::NotFound(format!(
+            "Could not find {:?}, there is nothing to migrate",
+            get_old_config_file_path_unchecked(config_dir)
+        )))?;
+    let old_config: OldConfig = serde_json::from_str(&fs::read_to_string(&old_config_path)?)
+        .map_err(|e| WechselError::Invalid(format!("Could not parse {old_config_path:?}: {e}")))?;
+    let home = home_dir().ok_or(io::Error::other("No Home dir found"))?;
+
+    let plan = MigrationPlan {
+        steps: plan_migration(&old_config, &old_config_path, &home)?,
+        active: old_config.active,
+    };
+
+    say!("Migrating the wechsel setup in {old_config_path:?}:");
+    for step in plan.steps.iter() {
+        say!("  {step}");
+    }
+    if dry_run {
+        return Ok(plan);
+    }
+    if !no_prompts
+        && !Confirm::new()
+            .with_prompt("Migrate?")
+            .default(false)
+            .interact()
+            .map_err(|_| WechselError::Cancelled)?
+    {
+        return Err(WechselError::Cancelled);
+    }
+
+    let _lock = ProjectLock::acquire(config_dir)?;
+    for step in plan.steps.iter() {
+        step.run()
+            .map_err(|e| io::Error::other(format!("{step} failed: {e}")))?;
+    }
+    say!("Migrated the wechsel setup");
+    Ok(plan)
+}
+
+/// The project to change to after a migration, the root project if the old active one is gone
+pub fn migrated_project(plan: MigrationPlan, config_dir: &Path) -> Result<String, WechselError> {
+    match find_project(&plan.active, config_dir) {
+        Ok(_) => Ok(plan.active),
+        Err(_) => Ok(get_project_tree(config_dir, false)?.prj_name),
+    }
+}
+
+fn plan_migration(
+    old_config: &OldConfig,
+    old_config_path: &Path,
+    home: &Path,
+) -> Result<Vec<MigrationStep>, WechselError> {
+    let mut steps = vec![];
+    let mut old_paths = vec![];
+    plan_project(
+        &old_config.all_prjs,
+        PathBuf::from(&old_config.all_prjs.path),
+        home,
+        home,
+        &mut steps,
+        &mut old_paths,
+    )?;
+
+    // The links of the old setup point into the old project folders
+    for entry in fs::read_dir(home)?.filter_map(|entry| entry.ok()) {
+        let Ok(target) = fs::read_link(entry.path()) else {
+            continue;
+        };
+        if old_paths
+            .iter()
+            .any(|old_path| target.starts_with(old_path))
+        {
+            steps.push(MigrationStep::RemoveLink { path: entry.path() });
+        }
+    }
+
+    let backup_name =
+        PathBuf::from(old_config_path.file_name().unwrap_or_default()).with_extension("json.bak");
+    steps.push(MigrationStep::Backup {
+        from: old_config_path.to_path_buf(),
+        to: free_path(
+            &OsFileSystem,
+            old_config_path.parent().unwrap_or(Path::new("/")),
+            &backup_name,
+        ),
+    });
+    Ok(steps)
+}
+
+fn plan_project(
+    prj: &OldProject,
+    old_path: PathBuf,
+    new_parent: &Path,
+    home: &Path,
+    steps: &mut Vec<MigrationStep>,
+    old_paths: &mut Vec<PathBuf>,
+) -> Result<(), WechselError> {
+    let new_path =
+        path_from_iter([new_parent, Path::new(&prj.name)]).with_extension(PROJECT_EXTENSION);
+    if new_path.exists() {
+        return Err(WechselError::Conflict(format!(
+            "Can't migrate {}, {new_path:?} already exists",
+            prj.name
+        )));
+    }
+    if !old_path.exists() {
+        warn!(
+            "The folder of {} ({old_path:?}) doesn't exist, it is migrated as an empty project",
+            prj.name
+        );
+    }
+    steps.push(MigrationStep::CreateDir {
+        path: new_path.clone(),
+    });
+
+    // Children first, they might be inside of a wechsel folder
+    for child in prj.children.iter() {
+        let child_old_path = path_from_iter([&old_path, &PathBuf::from(&child.path)]);
+        plan_project(child, child_old_path, &new_path, home, steps, old_paths)?;
+    }
+
+    let mut leftovers: Vec<PathBuf> = vec![];
+    for folder in prj.folder.iter() {
+        let from = path_from_iter([&old_path, &PathBuf::from(folder)]);
+        let Some(name) = from.file_name() else {
+            continue;
+        };
+        if !from.is_dir() {
+            warn!(
+                "Skipping the folder {from:?} of {}, it doesn't exist",
+                prj.name
+            );
+            continue;
+        }
+        let to = path_from_iter([new_path.as_path(), Path::new(name)])
+            .with_extension(WECHSEL_FOLDER_EXTENSION);
+        steps.push(MigrationStep::Move {
+            from: from.clone(),
+            to,
+        });
+        // Folders like `default/Desktop` leave an empty `default` behind
+        for ancestor in from.ancestors().skip(1) {
+            if !ancestor.starts_with(&old_path) || ancestor == old_path {
+                break;
+            }
+            if !leftovers.iter().any(|leftover| leftover == ancestor) {
+                leftovers.push(ancestor.to_path_buf());
+            }
+        }
+    }
+    // Deepest first, so the parents are empty once they are reached
+    leftovers.sort_by_key(|leftover| Reverse(leftover.components().count()));
+    steps.extend(
+        leftovers
+            .into_iter()
+            .map(|path| MigrationStep::RemoveIfEmpty { path }),
+    );
+
+    // The contents of the home folder itself stay where they are
+    if home.starts_with(&old_path) {
+        return Ok(());
+    }
+    if old_path.is_dir() {
+        steps.push(MigrationStep::MoveContents {
+            from: old_path.clone(),
+            to: new_path,
+        });
+        steps.push(MigrationStep::RemoveIfEmpty {
+            path: old_path.clone(),
+        });
+    }
+    old_paths.push(old_path);
+    Ok(())
+}
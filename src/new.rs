@@ -1,18 +1,23 @@
-use crate::utils::{
-    PROJECT_EXTENSION, WECHSEL_FOLDER_EXTENSION, path_from_iter, query_active_project,
-};
 use dialoguer::{Input, MultiSelect};
+use wechsel::{
+    FoundProject, PROJECT_EXTENSION, ProjectTreeNode, WechselError, Workspace, get_project_tree,
+    load_config,
+};
 
-use crate::tree::{ProjectTreeNode, get_project_tree, search_for_projects};
-use std::{collections::HashMap, fs, io, path::PathBuf};
+use crate::output::{hook_output, say};
+use crate::resolve::{project_containing, resolve_project};
+use std::{path::PathBuf, rc::Rc};
 
+/// Creates a project, asks for the parent and the folders if neither is given.
+/// Returns the folder of the new project.
 pub fn new_prj_cmd(
-    parent: Option<String>,
+    parent: Option<Rc<FoundProject>>,
     folders: Option<Vec<String>>,
     project_name: &str,
     config_dir: &PathBuf,
-) {
-    let pwd = std::env::current_dir().expect("Could not get current dir");
+) -> Result<PathBuf, WechselError> {
+    let workspace = Workspace::with_config_dir(config_dir)?.with_hook_output(hook_output());
+    let pwd = std::env::current_dir()?;
 
     // Check if the pwd folder is a project folder
     let found_parent = parent
@@ -21,29 +26,25 @@ pub fn new_prj_cmd(
             pwd.extension()
                 .map(|ext| ext == PROJECT_EXTENSION)
                 .unwrap_or_default()
-                .then(|| {
-                    // current dir is a project
-                    pwd.file_name()
-                        .and_then(|name| name.to_str())
-                        .expect("Could not get parent folder name")
-                        .to_string()
-                })
+                .then(|| project_containing(&pwd.canonicalize()?, &workspace))
         })
-        .flatten();
+        .flatten()
+        .transpose()?;
 
     let (parent, folders) = if parent.is_none() && folders.is_none() {
         // If no options are set, ask for them interactively
 
-        let parent = found_parent.unwrap_or_else(|| {
+        let parent = match found_parent {
+            Some(parent) => parent,
             // not in a wechsel project so the user has to supply a parent
-
-            let Ok(parent) = Input::new().with_prompt("Parent project").interact_text() else {
-                std::process::exit(1);
-            };
-            parent
-        });
-
-        //TODO Should check if parent exists
+            None => {
+                let parent: String = Input::new()
+                    .with_prompt("Parent project")
+                    .interact_text()
+                    .map_err(|_| WechselError::Cancelled)?;
+                resolve_project(&parent, &workspace)?
+            }
+        };
 
         fn collect_folders(folders: &mut Vec<String>, node: ProjectTreeNode) {
             if let Some(current_folders) = node.folders {
@@ -58,11 +59,17 @@ pub fn new_prj_cmd(
             }
         }
         let mut folders = vec![];
-        collect_folders(&mut folders, get_project_tree(config_dir, true));
+        collect_folders(&mut folders, get_project_tree(config_dir, true)?);
+        let new_folders = load_config(config_dir)?.general.new_folders;
+        let defaults: Vec<bool> = folders
+            .iter()
+            .map(|folder| new_folders.contains(folder))
+            .collect();
 
-        let Ok(folders) = MultiSelect::new()
+        let folders = MultiSelect::new()
             .with_prompt("Select folders to move to the new project")
             .items(&folders)
+            .defaults(&defaults)
             .report(false)
             .interact()
             .map(|i| {
@@ -70,9 +77,7 @@ pub fn new_prj_cmd(
                     .map(|i| folders[i].clone())
                     .collect::<Vec<_>>()
             })
-        else {
-            std::process::exit(1);
-        };
+            .map_err(|_| WechselError::Cancelled)?;
 
         let folders: Vec<String> = folders
             .into_iter()
@@ -82,81 +87,19 @@ pub fn new_prj_cmd(
         (parent, folders)
     } else {
         (
-            parent.unwrap_or(query_active_project().unwrap_or_default()),
-            folders.unwrap_or(vec!["Desktop".to_owned(), "Downloads".to_owned()]),
+            match parent {
+                Some(parent) => parent,
+                None => workspace.active_project().ok_or(WechselError::NotFound(
+                    "There is no active project to create the project in".to_string(),
+                ))?,
+            },
+            match folders {
+                Some(folders) => folders,
+                None => load_config(config_dir)?.general.new_folders,
+            },
         )
     };
 
-    create_new_prj(project_name, folders, parent, config_dir)
-        .expect("Could not create new project");
-}
-
-pub fn create_new_prj(
-    prj_name: &str,
-    folders: Vec<String>,
-    parent: String,
-    config_dir: &PathBuf,
-) -> io::Result<()> {
-    println!("Creating Project {prj_name:?}");
-
-    //get parent path
-    let [parent_path] = search_for_projects([&parent], config_dir);
-    let parent_path = &parent_path
-        .as_ref()
-        .unwrap_or_else(|| {
-            eprintln!("The given parent project could not be found");
-            std::process::exit(1);
-        })
-        .path;
-
-    let mut new_pr_path =
-        path_from_iter([parent_path, &PathBuf::from(prj_name)]).with_extension(PROJECT_EXTENSION);
-
-    // Create Project Folder
-    if !new_pr_path.exists() {
-        fs::create_dir_all(&new_pr_path).expect("Could not create Project Folder");
-    } else if !new_pr_path.is_dir() {
-        eprintln!(
-            "A file with the name of the new project exists in the place the project folder should be placed. Please either remove that file or specify a different name. {new_pr_path:?}"
-        )
-    }
-
-    // Create Subfolders
-    for subfolder in folders.iter() {
-        new_pr_path.push(PathBuf::from(subfolder).with_extension(WECHSEL_FOLDER_EXTENSION));
-
-        if !new_pr_path.exists() {
-            fs::create_dir(&new_pr_path).unwrap_or_else(|e| {
-                panic!(
-                    "Could not create Project Subfolder: {} {}",
-                    new_pr_path.to_str().unwrap(),
-                    e
-                )
-            });
-        }
-
-        new_pr_path.pop();
-    }
-
-    // Call on create script
-    let script = path_from_iter([config_dir, &PathBuf::from("on-prj-create")]);
-    if script.is_file() {
-        let env_vars: HashMap<String, String> = HashMap::from_iter(vec![
-            ("PRJ".to_owned(), prj_name.to_owned()),
-            (
-                "PRJ_PATH".to_owned(),
-                new_pr_path.to_str().unwrap_or_default().to_owned(),
-            ),
-        ]);
-        if let Ok(mut child) = std::process::Command::new("sh")
-            .envs(env_vars)
-            .arg("-c")
-            .arg(&script)
-            .current_dir(&new_pr_path)
-            .spawn()
-        {
-            let _ = child.wait();
-        }
-    }
-    Ok(())
+    say!("Creating Project {project_name:?}");
+    workspace.create_in(project_name, &parent.path, &folders)
 }
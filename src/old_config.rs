@@ -0,0 +1,28 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::path_from_iter;
+
+/// The `wechsel_projects.json` of wechsel <= 0.2.3
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OldConfig {
+    pub active: String,
+    pub all_prjs: OldProject,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OldProject {
+    pub name: String,
+    /// Absolute for the root project, relative to the parent project for all others
+    pub path: String,
+    /// The folders that were linked into the home folder, relative to the project path
+    #[serde(default)]
+    pub folder: Vec<String>,
+    #[serde(default)]
+    pub children: Vec<OldProject>,
+}
+
+pub fn get_old_config_file_path_unchecked(config_dir: &Path) -> PathBuf {
+    path_from_iter([config_dir, PathBuf::from("wechsel_projects.json").as_path()])
+}
@@ -0,0 +1,45 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use wechsel::{HookOutput, HookOutputSink};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Like `println!`, but silent with `-q` and when the result is printed as json, so stdout stays parseable
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if !crate::output::is_json() && !crate::output::is_quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use say;
+
+/// Shows the output of hooks as they print it, with `--json` all of it goes to stderr so stdout stays parseable
+pub fn hook_output() -> HookOutputSink {
+    HookOutputSink::new(|_, output, text| match output {
+        HookOutput::Stdout if !is_json() => {
+            print!("{text}");
+            let _ = io::stdout().flush();
+        }
+        HookOutput::Stdout | HookOutput::Stderr => eprint!("{text}"),
+        HookOutput::Message => eprintln!("{text}"),
+    })
+}
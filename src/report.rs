@@ -0,0 +1,225 @@
+use std::{collections::BTreeMap, path::Path};
+
+use chrono::{Datelike, Duration, NaiveTime, TimeZone};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::output::is_json;
+use wechsel::{
+    ProjectTreeNode, TimeEvent, TimeLogEntry, WechselError, get_project_tree, local_time, now,
+    parse_time, read_time_log,
+};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum GroupBy {
+    Day,
+    Week,
+    Project,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+#[derive(Serialize)]
+pub struct ReportRow {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period: Option<String>,
+    pub project: String,
+    pub seconds: i64,
+}
+
+pub struct ReportOptions {
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub by: GroupBy,
+    pub format: ReportFormat,
+    pub rollup: Option<usize>,
+}
+
+/// Prints the report in the requested format and returns its rows
+pub fn report(options: ReportOptions, config_dir: &Path) -> Result<Vec<ReportRow>, WechselError> {
+    let since = options
+        .since
+        .map(|since| parse_time(&since))
+        .transpose()?
+        .unwrap_or(i64::MIN);
+    let until = options
+        .until
+        .map(|until| parse_time(&until))
+        .transpose()?
+        .unwrap_or(now());
+
+    let intervals = tracked_intervals(&read_time_log(), now());
+
+    // Map every project to the ancestor it should be counted for
+    let rollup = match options.rollup {
+        Some(depth) => {
+            let mut ancestors = BTreeMap::new();
+            collect_ancestors(
+                get_project_tree(config_dir, false)?,
+                &mut vec![],
+                depth,
+                &mut ancestors,
+            );
+            Some(ancestors)
+        }
+        None => None,
+    };
+
+    let mut totals: BTreeMap<(Option<String>, String), i64> = BTreeMap::new();
+    for (project, start, end) in intervals {
+        // Projects that were active for less than a second are still listed, with 0 seconds
+        let empty = start == end;
+        let (start, end) = (start.max(since), end.min(until));
+        if start > end || (start == end && !empty) {
+            continue;
+        }
+        let project = rollup
+            .as_ref()
+            .and_then(|ancestors| ancestors.get(&project).cloned())
+            .unwrap_or(project);
+
+        for (period, seconds) in split_by_period(start, end, options.by) {
+            *totals.entry((period, project.clone())).or_default() += seconds;
+        }
+    }
+
+    let rows: Vec<ReportRow> = totals
+        .into_iter()
+        .map(|((period, project), seconds)| ReportRow {
+            period,
+            project,
+            seconds,
+        })
+        .collect();
+
+    if is_json() {
+        return Ok(rows);
+    }
+    match options.format {
+        ReportFormat::Text => {
+            for row in &rows {
+                let duration = format!("{}h {:02}m", row.seconds / 3600, row.seconds / 60 % 60);
+                match &row.period {
+                    Some(period) => println!("{period:<12}{:<24}{duration:>10}", row.project),
+                    None => println!("{:<24}{duration:>10}", row.project),
+                }
+            }
+        }
+        ReportFormat::Csv => {
+            match options.by {
+                GroupBy::Project => println!("project,seconds"),
+                _ => println!("period,project,seconds"),
+            }
+            for row in &rows {
+                match &row.period {
+                    Some(period) => {
+                        println!("{period},{},{}", csv_field(&row.project), row.seconds)
+                    }
+                    None => println!("{},{}", csv_field(&row.project), row.seconds),
+                }
+            }
+        }
+        ReportFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&rows).unwrap_or_default()
+        ),
+    }
+    Ok(rows)
+}
+
+/// Turns the log into (project, start, end) intervals of tracked time
+pub fn tracked_intervals(entries: &[TimeLogEntry], now: i64) -> Vec<(String, i64, i64)> {
+    let mut intervals = vec![];
+    let mut current: Option<&str> = None;
+    let mut paused = false;
+    let mut start = 0;
+
+    for entry in entries {
+        // The log already has the new names, a rename doesn't end the time of the project
+        if let TimeEvent::Rename { .. } = entry.event {
+            continue;
+        }
+        if let (Some(project), false) = (current, paused) {
+            intervals.push((project.to_string(), start, entry.time));
+        }
+        match &entry.event {
+            TimeEvent::Switch { project, .. } => {
+                current = Some(project);
+                paused = false;
+            }
+            TimeEvent::Pause => paused = true,
+            TimeEvent::Resume => paused = false,
+            TimeEvent::Rename { .. } => {}
+        }
+        start = entry.time;
+    }
+    if let (Some(project), false) = (current, paused) {
+        if now > start {
+            intervals.push((project.to_string(), start, now));
+        }
+    }
+    intervals
+}
+
+fn split_by_period(start: i64, end: i64, by: GroupBy) -> Vec<(Option<String>, i64)> {
+    if let GroupBy::Project = by {
+        return vec![(None, end - start)];
+    }
+
+    let mut parts = vec![];
+    let mut start = start;
+    while let Some(day) = local_time(start).map(|time| time.date_naive()) {
+        let next_day = day
+            .checked_add_signed(Duration::days(1))
+            .and_then(|next| {
+                chrono::Local
+                    .from_local_datetime(&next.and_time(NaiveTime::MIN))
+                    .earliest()
+            })
+            .map(|next| next.timestamp())
+            .unwrap_or(end);
+        let part_end = next_day.min(end);
+
+        let period = match by {
+            GroupBy::Week => {
+                let week = day.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            _ => day.format("%Y-%m-%d").to_string(),
+        };
+        parts.push((Some(period), part_end - start));
+        start = part_end;
+        if start >= end {
+            break;
+        }
+    }
+    parts
+}
+
+fn collect_ancestors(
+    node: ProjectTreeNode,
+    chain: &mut Vec<String>,
+    depth: usize,
+    ancestors: &mut BTreeMap<String, String>,
+) {
+    chain.push(node.prj_name.clone());
+    let target = chain.get(depth).unwrap_or(&node.prj_name).clone();
+    ancestors.insert(node.prj_name, target);
+    for child in node.children {
+        collect_ancestors(child, chain, depth, ancestors);
+    }
+    chain.pop();
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use dialoguer::Select;
+use wechsel::{
+    FoundProject, History, OsFileSystem, PROJECT_EXTENSION, ProjectTreeNode, WechselError,
+    Workspace,
+};
+
+/// Turns a project argument into a project.
+/// Besides plain names this accepts `-` for the previously active project and `.` or any
+/// path inside a project folder for the project containing that path.
+pub fn resolve_project(
+    target: &str,
+    workspace: &Workspace,
+) -> Result<Rc<FoundProject>, WechselError> {
+    if target == "-" {
+        let previous = History::load(&OsFileSystem, workspace.config_dir())
+            .previous()
+            .filter(|prj| !prj.is_empty())
+            .map(str::to_string)
+            .ok_or(WechselError::NotFound(
+                "There is no previously active project".to_string(),
+            ))?;
+        return workspace.find(&previous);
+    }
+
+    if !is_path(target) {
+        return workspace.find(target);
+    }
+
+    let path = PathBuf::from(target)
+        .canonicalize()
+        .map_err(|e| WechselError::NotFound(format!("Could not resolve path {target:?}: {e}")))?;
+    project_containing(&path, workspace)
+}
+
+/// The project whose folder contains `path`, which has to be canonical
+pub fn project_containing(
+    path: &Path,
+    workspace: &Workspace,
+) -> Result<Rc<FoundProject>, WechselError> {
+    let mut projects = vec![];
+    flatten_tree(workspace.tree(false)?, &mut projects);
+
+    // Walk up to the nearest project folder, projects that are symlinked into
+    // the tree don't carry the extension in their canonical path
+    for ancestor in path.ancestors() {
+        if let Some((prj_path, _)) = projects.iter().find(|(_, path)| path == ancestor) {
+            return project_at(prj_path, workspace);
+        }
+        if ancestor
+            .extension()
+            .map(|ext| ext == PROJECT_EXTENSION)
+            .unwrap_or(false)
+        {
+            return Err(WechselError::NotFound(format!(
+                "The project folder {ancestor:?} is not part of the project tree"
+            )));
+        }
+    }
+
+    Err(WechselError::NotFound(format!(
+        "{path:?} is not inside of a project"
+    )))
+}
+
+pub fn back_target(workspace: &Workspace) -> Result<Rc<FoundProject>, WechselError> {
+    let name = History::load(&OsFileSystem, workspace.config_dir())
+        .back
+        .pop()
+        .ok_or(WechselError::NotFound(
+            "There is no project to go back to".to_string(),
+        ))?;
+    workspace.find(&name)
+}
+
+pub fn forward_target(workspace: &Workspace) -> Result<Rc<FoundProject>, WechselError> {
+    let name = History::load(&OsFileSystem, workspace.config_dir())
+        .forward
+        .pop()
+        .ok_or(WechselError::NotFound(
+            "There is no project to go forward to".to_string(),
+        ))?;
+    workspace.find(&name)
+}
+
+pub fn up_target(workspace: &Workspace) -> Result<Rc<FoundProject>, WechselError> {
+    let active = active_project(workspace)?;
+    active.parent.clone().ok_or_else(|| {
+        WechselError::NotFound(format!("The project {} has no parent project", active.name))
+    })
+}
+
+pub fn down_target(workspace: &Workspace) -> Result<Rc<FoundProject>, WechselError> {
+    let active = active_project(workspace)?;
+
+    fn find(node: ProjectTreeNode, path: &Path) -> Option<ProjectTreeNode> {
+        if node.path == path {
+            return Some(node);
+        }
+        node.children
+            .into_iter()
+            .find_map(|child| find(child, path))
+    }
+
+    let mut children: Vec<ProjectTreeNode> = find(workspace.tree(false)?, &active.path)
+        .map(|node| node.children)
+        .unwrap_or_default();
+
+    let child = match children.len() {
+        0 => {
+            return Err(WechselError::NotFound(format!(
+                "The project {} has no child projects",
+                active.name
+            )));
+        }
+        1 => children.remove(0),
+        _ => {
+            let names: Vec<&str> = children
+                .iter()
+                .map(|child| child.prj_name.as_str())
+                .collect();
+            let idx = Select::new()
+                .with_prompt("Select child project")
+                .items(&names)
+                .default(0)
+                .interact()
+                .map_err(|_| WechselError::Cancelled)?;
+            children.remove(idx)
+        }
+    };
+    project_at(&child.path, workspace)
+}
+
+fn active_project(workspace: &Workspace) -> Result<Rc<FoundProject>, WechselError> {
+    workspace.active_project().ok_or(WechselError::NotFound(
+        "There is no active project".to_string(),
+    ))
+}
+
+/// The project with the folder `path`, the way it is in the tree
+pub fn project_at(path: &Path, workspace: &Workspace) -> Result<Rc<FoundProject>, WechselError> {
+    workspace
+        .find_by_path(path)?
+        .ok_or(WechselError::NotFound(format!(
+            "There is no project at {path:?}"
+        )))
+}
+
+fn is_path(target: &str) -> bool {
+    target == "." || target == ".." || target.contains('/')
+}
+
+// The path of each project in the tree and its canonical path
+fn flatten_tree(node: ProjectTreeNode, projects: &mut Vec<(PathBuf, PathBuf)>) {
+    if let Ok(path) = node.path.canonicalize() {
+        projects.push((node.path, path));
+    }
+    for child in node.children {
+        flatten_tree(child, projects);
+    }
+}
@@ -0,0 +1,151 @@
+use std::{collections::HashMap, fs, io};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use log::debug;
+use serde::{Deserialize, Serialize};
+
+use crate::error::WechselError;
+use crate::file_system::{FileSystem, OsFileSystem};
+use crate::utils::{get_state_dir, time_log_path};
+
+#[derive(Serialize, Deserialize)]
+pub struct TimeLogEntry {
+    // Unix timestamp in seconds
+    pub time: i64,
+    #[serde(flatten)]
+    pub event: TimeEvent,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum TimeEvent {
+    Switch {
+        project: String,
+        // Set for changes made by `wechsel auto` or the daemon
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        auto: bool,
+    },
+    Pause,
+    Resume,
+    /// The log is only appended to, switches to `from` before this count for `to`
+    Rename {
+        from: String,
+        to: String,
+    },
+}
+
+pub fn append_time_event(fs: &dyn FileSystem, event: TimeEvent, time: i64) -> io::Result<()> {
+    let state_dir = get_state_dir().ok_or(io::Error::other("No state dir found"))?;
+    fs.create_dir_all(&state_dir)?;
+
+    let line = serde_json::to_string(&TimeLogEntry { time, event })?;
+    fs.append(&time_log_path(&state_dir), format!("{line}\n").as_bytes())?;
+    debug!("Appended {line} to the time log");
+    Ok(())
+}
+
+/// Reads the whole log sorted by time, lines that can't be parsed are skipped.
+/// Switches to a project that was renamed later have the name it has now.
+pub fn read_time_log() -> Vec<TimeLogEntry> {
+    let mut entries: Vec<TimeLogEntry> = get_state_dir()
+        .and_then(|state_dir| fs::read_to_string(time_log_path(&state_dir)).ok())
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    // Stable, so events with the same timestamp keep the order they were logged in
+    entries.sort_by_key(|entry| entry.time);
+
+    // From the end, so a project that was renamed several times ends up with its last name
+    // and a new project with the old name keeps its own switches
+    let mut renamed: HashMap<String, String> = HashMap::new();
+    for entry in entries.iter_mut().rev() {
+        match &mut entry.event {
+            TimeEvent::Rename { from, to } => {
+                let to = renamed.get(to).unwrap_or(to).clone();
+                renamed.insert(from.clone(), to);
+            }
+            TimeEvent::Switch { project, .. } => {
+                if let Some(to) = renamed.get(project) {
+                    *project = to.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// The most recent project change that was made by the user and not by rules
+pub fn last_manual_switch() -> Option<i64> {
+    read_time_log()
+        .into_iter()
+        .rev()
+        .find_map(|entry| match entry.event {
+            TimeEvent::Switch { auto: false, .. } => Some(entry.time),
+            _ => None,
+        })
+}
+
+/// Marks the time from `from` to `to` as a pause, both default to now.
+/// Without `to` the pause lasts until the next switch or `wechsel resume`.
+pub fn pause(from: Option<String>, to: Option<String>) -> Result<(), WechselError> {
+    let from = from
+        .map(|from| parse_time(&from))
+        .transpose()?
+        .unwrap_or(now());
+    let to = to.map(|to| parse_time(&to)).transpose()?;
+    if to.is_some_and(|to| to < from) {
+        return Err(WechselError::Invalid(
+            "The end of the pause has to be after its start".to_string(),
+        ));
+    }
+
+    append_time_event(&OsFileSystem, TimeEvent::Pause, from)?;
+    if let Some(to) = to {
+        append_time_event(&OsFileSystem, TimeEvent::Resume, to)?;
+    }
+    Ok(())
+}
+
+pub fn now() -> i64 {
+    Local::now().timestamp()
+}
+
+/// Parses `YYYY-MM-DD`, `YYYY-MM-DD HH:MM`, `HH:MM` (today), `today` and `yesterday`
+/// as local time.
+pub fn parse_time(input: &str) -> Result<i64, WechselError> {
+    let today = Local::now().date_naive();
+    let datetime = match input {
+        "today" => Some(today.and_time(NaiveTime::MIN)),
+        "yesterday" => today.pred_opt().map(|day| day.and_time(NaiveTime::MIN)),
+        _ => NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M")
+            .ok()
+            .or_else(|| {
+                NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                    .ok()
+                    .map(|day| day.and_time(NaiveTime::MIN))
+            })
+            .or_else(|| {
+                NaiveTime::parse_from_str(input, "%H:%M")
+                    .ok()
+                    .map(|time| today.and_time(time))
+            }),
+    };
+
+    datetime
+        .and_then(|datetime| Local.from_local_datetime(&datetime).earliest())
+        .map(|datetime| datetime.timestamp())
+        .ok_or_else(|| {
+            WechselError::Invalid(format!(
+                "Could not parse time {input:?}, expected YYYY-MM-DD, \"YYYY-MM-DD HH:MM\", HH:MM, today or yesterday"
+            ))
+        })
+}
+
+pub fn local_time(timestamp: i64) -> Option<DateTime<Local>> {
+    Local.timestamp_opt(timestamp, 0).single()
+}
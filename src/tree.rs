@@ -1,36 +1,61 @@
 use std::{
-    array, fs,
+    array,
     path::{Path, PathBuf},
     rc::Rc,
 };
 
-#[cfg(feature = "deserial")]
-use serde::Deserialize;
-use serde::Serialize;
+use schemars::{schema_for, JsonSchema};
+use serde::{Deserialize, Serialize};
 
-use crate::utils::{
-    is_entry_folder_with_extension, path_from_iter, PROJECT_EXTENSION, WECHSEL_FOLDER_EXTENSION,
-};
+use crate::config::load_config;
+use crate::error::WechselError;
+use crate::file_system::FileSystem;
+use crate::old_config::get_old_config_file_path_unchecked;
+use crate::utils::{is_folder_with_extension, PROJECT_EXTENSION, WECHSEL_FOLDER_EXTENSION};
+use crate::workspace::Workspace;
+
+/// Raised whenever the tree output changes in a way that could break its consumers
+pub const TREE_SCHEMA_VERSION: u32 = 1;
 
-#[derive(Serialize)]
-#[cfg_attr(feature = "deserial", derive(Deserialize, Debug))]
+/// The output of `wechsel tree`
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct TreeOutput {
+    /// Version of this format, it is raised on changes that could break consumers
+    pub schema_version: u32,
     pub tree: ProjectTreeNode,
+    /// Name of the active project, empty if there is none
     pub active: String,
 }
-#[derive(Debug, Serialize)]
-#[cfg_attr(feature = "deserial", derive(Deserialize))]
+
+impl TreeOutput {
+    pub fn new(tree: ProjectTreeNode, active: String) -> TreeOutput {
+        TreeOutput {
+            schema_version: TREE_SCHEMA_VERSION,
+            tree,
+            active,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ProjectTreeNode {
-    #[serde(rename(serialize = "name", deserialize = "name"))]
+    #[serde(rename = "name")]
     pub prj_name: String,
     pub children: Vec<ProjectTreeNode>,
     pub path: PathBuf,
-    #[serde(skip_serializing_if = "Option::is_none")]
+    /// The wechsel folders of the project, only with `--folders`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub folders: Option<Vec<String>>,
 }
 
+/// JSON Schema of the tree output, `schema/tree.schema.json` is generated from it
+pub fn tree_schema() -> serde_json::Value {
+    serde_json::to_value(schema_for!(TreeOutput)).unwrap_or_default()
+}
+
 pub struct FoundProject {
     pub parent: Option<Rc<FoundProject>>,
+    pub name: String,
     pub path: PathBuf,
 }
 
@@ -42,11 +67,15 @@ fn recursion_fn<
 >(
     lambda: F,
     lambda_parent: F2,
+    fs: &dyn FileSystem,
+    home: &Path,
     config_dir: &PathBuf,
     collect_folders: bool,
-) -> Out {
-    let home = dirs::home_dir().expect("Could not find home directory");
+) -> Result<Out, WechselError> {
+    // The name of the home folder, when it is the root project itself
+    let root_name = load_config(config_dir)?.general.root_project;
 
+    #[allow(clippy::too_many_arguments)]
     fn inner<
         Out,
         Parent,
@@ -58,11 +87,12 @@ fn recursion_fn<
         lambda: &F,
         parent: Option<Rc<Parent>>,
         lambda_parent: &F2,
-        config_dir: &PathBuf,
+        fs: &dyn FileSystem,
         collect_folders: bool,
+        root_name: &str,
     ) -> Out {
         let prj_name = if depth == 0 {
-            "home".to_string()
+            root_name.to_string()
         } else {
             path.file_stem()
                 .and_then(|name| name.to_str())
@@ -74,111 +104,165 @@ fn recursion_fn<
 
         let mut folders = vec![];
 
-        // is set to true after depth 0 to stop all the checks that are not needed after depth 0
-        let mut has_wechsel_folder = depth != 0;
-
-        let mut children: Vec<_> =
-            fs::read_dir(&path)
-                .map(|children| {
-                    children
-                        .into_iter()
-                        .filter_map(|child| {
-                            if collect_folders {
-                                if is_entry_folder_with_extension(&child, WECHSEL_FOLDER_EXTENSION)
-                                    .is_some()
-                                {
-                                    if let Some(folder) =
-                                        child.as_ref().unwrap().path().file_stem().and_then(
-                                            |stem| stem.to_str().map(|str| str.to_string()),
-                                        )
-                                    {
-                                        folders.push(folder);
-                                    }
-                                    has_wechsel_folder = true;
-                                }
-                            } else if !has_wechsel_folder
-                                && is_entry_folder_with_extension(&child, WECHSEL_FOLDER_EXTENSION)
-                                    .is_some()
+        let children: Vec<_> = fs
+            .read_dir(&path)
+            .map(|children| {
+                children
+                    .into_iter()
+                    .filter_map(|child| {
+                        if collect_folders
+                            && is_folder_with_extension(fs, &child, WECHSEL_FOLDER_EXTENSION)
+                        {
+                            if let Some(folder) = child
+                                .file_stem()
+                                .and_then(|stem| stem.to_str().map(|str| str.to_string()))
                             {
-                                has_wechsel_folder = true;
+                                folders.push(folder);
                             }
+                        }
 
-                            is_entry_folder_with_extension(&child, PROJECT_EXTENSION).map(|child| {
-                                inner(
-                                    child.path().clone(),
-                                    depth + 1,
-                                    lambda,
-                                    Some(parent_out.clone()),
-                                    lambda_parent,
-                                    config_dir,
-                                    collect_folders,
-                                )
-                            })
+                        is_folder_with_extension(fs, &child, PROJECT_EXTENSION).then(|| {
+                            inner(
+                                child,
+                                depth + 1,
+                                lambda,
+                                Some(parent_out.clone()),
+                                lambda_parent,
+                                fs,
+                                collect_folders,
+                                root_name,
+                            )
                         })
-                        .collect()
-                })
-                .unwrap_or_default();
-
-        if !has_wechsel_folder && children.len() == 1 {
-            // Extra rule for depth 0, if there is only one child project and no wechsel folders take it out of the tree
-            children.remove(0)
-        } else if !has_wechsel_folder
-            && children.is_empty()
-            && get_old_config_file_path(config_dir).is_some()
-        {
-            eprintln!(
-                "Your wechsel setup seems to be setup for an old version of wechsel, please migrate to the new wechsel setup, to do this you might want to downgrade wechsel to version <= 0.2.3 and call wechsel migrate"
-            );
-            std::process::exit(1);
-        } else if !has_wechsel_folder && children.is_empty() {
-            eprintln!(
-                "Wechsel could not find any projects in your home directory, are you sure you initialized wechsel already? (wechsel init)"
-            );
-            std::process::exit(1);
-        } else {
-            lambda(prj_name, children, path, parent_out, folders)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        lambda(prj_name, children, path, parent_out, folders)
+    }
+
+    // Checks that only apply to the home folder
+    let mut has_wechsel_folder = false;
+    let mut projects = vec![];
+    for child in fs.read_dir(home)? {
+        if is_folder_with_extension(fs, &child, WECHSEL_FOLDER_EXTENSION) {
+            has_wechsel_folder = true;
+        } else if is_folder_with_extension(fs, &child, PROJECT_EXTENSION) {
+            projects.push(child);
         }
     }
-    inner(
-        home,
-        0,
-        &lambda,
-        None,
-        &lambda_parent,
-        config_dir,
-        collect_folders,
-    )
+
+    match (has_wechsel_folder, projects.len()) {
+        // If there is only one project and no wechsel folders in the home folder, that project is the root
+        (false, 1) => Ok(inner(
+            projects.remove(0),
+            1,
+            &lambda,
+            None,
+            &lambda_parent,
+            fs,
+            collect_folders,
+            &root_name,
+        )),
+        (false, 0) if get_old_config_file_path(config_dir).is_some() => Err(WechselError::OldSetup),
+        (false, 0) => Err(WechselError::NotInitialized),
+        _ => Ok(inner(
+            home.to_path_buf(),
+            0,
+            &lambda,
+            None,
+            &lambda_parent,
+            fs,
+            collect_folders,
+            &root_name,
+        )),
+    }
 }
 
-pub fn search_for_projects<const N: usize>(
+pub(crate) fn find_all_projects<const N: usize>(
     targets: [&str; N],
+    fs: &dyn FileSystem,
+    home: &Path,
     config_dir: &PathBuf,
-) -> [Option<Rc<FoundProject>>; N] {
+) -> Result<[Vec<Rc<FoundProject>>; N], WechselError> {
     recursion_fn(
-        |name, children: Vec<[Option<Rc<FoundProject>>; N]>, _, me, _| {
-            let mut found: [Option<Rc<FoundProject>>; N] = array::from_fn(|_| None);
+        |name, children: Vec<[Vec<Rc<FoundProject>>; N]>, _, me: Rc<FoundProject>, _| {
+            let mut found: [Vec<Rc<FoundProject>>; N] = array::from_fn(|_| vec![]);
             for child in children {
-                for (idx, a) in child.iter().enumerate() {
-                    if let Some(a) = a {
-                        found[idx] = Some(a.clone());
-                    }
+                for (idx, matches) in child.into_iter().enumerate() {
+                    found[idx].extend(matches);
                 }
             }
-            if let Some(idx) = targets.iter().position(|target| target == &name.as_str()) {
-                found[idx] = Some(me)
+            for (idx, target) in targets.iter().enumerate() {
+                if target == &name.as_str() {
+                    found[idx].push(me.clone());
+                }
             }
             found
         },
-        |_, path, parent| FoundProject {
+        |name, path, parent| FoundProject {
+            name: name.clone(),
             path: path.clone(),
             parent,
         },
+        fs,
+        home,
         config_dir,
         false,
     )
 }
 
-pub fn get_project_tree(config_dir: &PathBuf, collect_folders: bool) -> ProjectTreeNode {
+pub(crate) fn find_project_by_path(
+    path: &Path,
+    fs: &dyn FileSystem,
+    home: &Path,
+    config_dir: &PathBuf,
+) -> Result<Option<Rc<FoundProject>>, WechselError> {
+    recursion_fn(
+        |_, children: Vec<Option<Rc<FoundProject>>>, prj_path, me: Rc<FoundProject>, _| {
+            if prj_path == path {
+                Some(me)
+            } else {
+                children.into_iter().flatten().next()
+            }
+        },
+        |name, path, parent| FoundProject {
+            name: name.clone(),
+            path: path.clone(),
+            parent,
+        },
+        fs,
+        home,
+        config_dir,
+        false,
+    )
+}
+
+pub fn search_for_projects<const N: usize>(
+    targets: [&str; N],
+    config_dir: &Path,
+) -> Result<[Option<Rc<FoundProject>>; N], WechselError> {
+    Workspace::with_config_dir(config_dir)?.search(targets)
+}
+
+/// Finds the single project with the given name
+pub fn find_project(name: &str, config_dir: &Path) -> Result<Rc<FoundProject>, WechselError> {
+    Workspace::with_config_dir(config_dir)?.find(name)
+}
+
+pub fn get_project_tree(
+    config_dir: &Path,
+    collect_folders: bool,
+) -> Result<ProjectTreeNode, WechselError> {
+    Workspace::with_config_dir(config_dir)?.tree(collect_folders)
+}
+
+pub(crate) fn project_tree(
+    fs: &dyn FileSystem,
+    home: &Path,
+    config_dir: &PathBuf,
+    collect_folders: bool,
+) -> Result<ProjectTreeNode, WechselError> {
     recursion_fn(
         |prj_name, children, path, _, folders| ProjectTreeNode {
             prj_name,
@@ -187,12 +271,14 @@ pub fn get_project_tree(config_dir: &PathBuf, collect_folders: bool) -> ProjectT
             folders: (!folders.is_empty()).then_some(folders),
         },
         |_, _, _| (),
+        fs,
+        home,
         config_dir,
         collect_folders,
     )
 }
 
 pub fn get_old_config_file_path(config_dir: &Path) -> Option<PathBuf> {
-    let path = path_from_iter([config_dir, PathBuf::from("wechsel_projects.json").as_path()]);
+    let path = get_old_config_file_path_unchecked(config_dir);
     path.exists().then_some(path)
 }
@@ -0,0 +1,144 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::file_system::FileSystem;
+use crate::hooks::{ON_ENTER_HOOK, ON_LEAVE_HOOK, PRE_CHANGE_HOOK, hook_scripts};
+use crate::utils::{path_from_iter, trust_path};
+
+/// Run by the default on-prj-change script of a project
+pub const PROJECT_ON_CHANGE_SCRIPT: &str = ".on-prj-change";
+/// Sourced by the bash snippet in the active project
+pub const INIT_SCRIPT: &str = ".init-prj";
+/// Sourced by the fish snippet in the active project
+pub const FISH_INIT_SCRIPT: &str = ".init-prj.fish";
+
+/// The hashes of the project scripts that were allowed with `wechsel allow`.
+/// Scripts in project folders only run while they are unchanged since then.
+#[derive(Default, Serialize, Deserialize)]
+pub struct TrustStore {
+    scripts: BTreeMap<PathBuf, String>,
+}
+
+impl TrustStore {
+    /// A missing or unreadable file allows nothing
+    pub fn load(fs: &dyn FileSystem, config_dir: &PathBuf) -> TrustStore {
+        fs.read(&trust_path(config_dir))
+            .ok()
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, fs: &dyn FileSystem, config_dir: &PathBuf) -> io::Result<()> {
+        fs.write(&trust_path(config_dir), &serde_json::to_vec(self)?)?;
+        debug!("Wrote {:?}", trust_path(config_dir));
+        Ok(())
+    }
+
+    /// Whether `script` was allowed and hasn't changed since
+    pub fn is_allowed(&self, fs: &dyn FileSystem, script: &Path) -> bool {
+        fs.read(script)
+            .map(|content| self.allows(script, &content))
+            .unwrap_or_default()
+    }
+
+    /// Whether `script` was allowed with `content`, for callers that run what they checked
+    pub fn allows(&self, script: &Path, content: &[u8]) -> bool {
+        self.scripts
+            .get(script)
+            .is_some_and(|allowed| *allowed == content_hash(content))
+    }
+
+    /// The hash `script` was allowed with, if it hasn't changed since
+    pub fn allowed_hash(&self, fs: &dyn FileSystem, script: &Path) -> Option<&str> {
+        self.scripts
+            .get(script)
+            .filter(|_| self.is_allowed(fs, script))
+            .map(String::as_str)
+    }
+
+    /// Records the current hash of `script`
+    pub fn allow(&mut self, fs: &dyn FileSystem, script: &Path) -> io::Result<String> {
+        let hash = file_hash(fs, script)?;
+        self.scripts.insert(script.to_path_buf(), hash.clone());
+        Ok(hash)
+    }
+
+    /// Follows a project folder that was renamed, its child projects move along with it.
+    /// Returns whether any allowed script was in there.
+    pub fn moved(&mut self, from: &Path, to: &Path) -> bool {
+        let moved: Vec<PathBuf> = self
+            .scripts
+            .keys()
+            .filter(|script| script.starts_with(from))
+            .cloned()
+            .collect();
+        for script in moved.iter() {
+            if let (Ok(rest), Some(hash)) = (script.strip_prefix(from), self.scripts.remove(script))
+            {
+                self.scripts.insert(to.join(rest), hash);
+            }
+        }
+        !moved.is_empty()
+    }
+
+    /// Forgets the scripts of the project in `folder`, returns the ones that were allowed
+    pub fn deny(&mut self, folder: &Path) -> Vec<PathBuf> {
+        let denied: Vec<PathBuf> = self
+            .scripts
+            .keys()
+            .filter(|script| is_script_of(script, folder))
+            .cloned()
+            .collect();
+        for script in denied.iter() {
+            self.scripts.remove(script);
+        }
+        denied
+    }
+}
+
+// Scripts of child projects are in their own folders, only hook directories are looked into
+fn is_script_of(script: &Path, folder: &Path) -> bool {
+    match script.parent() {
+        Some(parent) if parent == folder => true,
+        Some(parent) => {
+            parent.extension().is_some_and(|ext| ext == "d") && parent.parent() == Some(folder)
+        }
+        None => false,
+    }
+}
+
+/// The sha256 of a file as hex
+pub fn file_hash(fs: &dyn FileSystem, path: &Path) -> io::Result<String> {
+    Ok(content_hash(&fs.read(path)?))
+}
+
+/// The sha256 of `content` as hex
+pub fn content_hash(content: &[u8]) -> String {
+    let hash = Sha256::digest(content);
+    hash.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// The scripts in a project folder that only run after they were allowed
+pub fn project_scripts(fs: &dyn FileSystem, prj_path: &Path) -> Vec<PathBuf> {
+    let mut scripts = vec![];
+    for hook in [PRE_CHANGE_HOOK, ON_LEAVE_HOOK, ON_ENTER_HOOK] {
+        scripts.extend(hook_scripts(
+            fs,
+            &path_from_iter([prj_path, Path::new(hook)]),
+        ));
+    }
+    for script in [PROJECT_ON_CHANGE_SCRIPT, INIT_SCRIPT, FISH_INIT_SCRIPT] {
+        let script = path_from_iter([prj_path, Path::new(script)]);
+        if fs.is_file(&script) {
+            scripts.push(script);
+        }
+    }
+    scripts
+}
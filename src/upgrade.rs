@@ -0,0 +1,256 @@
+use std::{
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+};
+
+use dialoguer::Confirm;
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::change::switch_project;
+use crate::doctor::{ENV_FILE, MISSPELLED_ENV_FILE};
+use crate::migrate::{migrate, migrated_project};
+use crate::output::{is_json, say};
+use wechsel::{
+    ChangeOptions, Navigation, WechselError, bashrc_path, find_project, fish_config_path,
+    get_old_config_file_path, get_project_tree, layout_version_path, load_config,
+};
+
+/// The layout version this wechsel creates and expects
+pub const LAYOUT_VERSION: u32 = 3;
+
+/// A step that brings the setup from `version - 1` to `version`, it has to be safe to run twice
+pub struct Upgrade {
+    pub version: u32,
+    pub description: &'static str,
+    pub apply: fn(&PathBuf) -> Result<(), WechselError>,
+}
+
+/// All upgrades in the order they have to run
+pub static UPGRADES: [Upgrade; 3] = [
+    Upgrade {
+        version: 1,
+        description: "Move the projects of wechsel_projects.json into .p and .w folders",
+        apply: migrate_projects_file,
+    },
+    Upgrade {
+        version: 2,
+        description: "Source environment_variables instead of enviroment_variables in the shell snippets",
+        apply: fix_env_file_name,
+    },
+    Upgrade {
+        version: 3,
+        description: "Only run the .init-prj and .on-prj-change scripts of projects that were allowed",
+        apply: use_allowed_scripts,
+    },
+];
+
+// The parts of the snippets and the default on-prj-change script that ran project scripts unchecked,
+// the checked ones only run them with the hash they were allowed with
+const UNCHECKED_SCRIPTS: [(&str, &str); 3] = [
+    (
+        "init=$PRJ_PATH/.init-prj\nif [ -f $init ]; then\n    . $init\n",
+        r#"init=$PRJ_INIT
+if [ -n "$init" ] && [ -f "$init" ] && [ -n "$PRJ_INIT_SHA256" ]; then
+    # Hashed and sourced from a private copy, so the script can't change in between
+    init_copy=$(mktemp)
+    cat "$init" > "$init_copy"
+    [ "$({ sha256sum || shasum -a 256; } < "$init_copy" 2>/dev/null | cut -d' ' -f1)" = "$PRJ_INIT_SHA256" ] && . "$init_copy"
+    rm -f "$init_copy"
+"#,
+    ),
+    (
+        "set init $PRJ_PATH/.init-prj.fish\n    if test -e $init\n        source $init\n",
+        r#"set init $PRJ_INIT_FISH
+    if test -n "$init"; and test -e "$init"
+        # Hashed and sourced from a private copy, so the script can't change in between
+        set -l copy (mktemp)
+        cat $init > $copy
+        set -l hash (begin; sha256sum; or shasum -a 256; end < $copy 2>/dev/null | cut -d' ' -f1)
+        test -n "$hash"; and test "$hash" = "$PRJ_INIT_FISH_SHA256"; and source $copy
+        rm -f $copy
+"#,
+    ),
+    (
+        "on_change=$PRJ_PATH/.on-prj-change\nif [ -f $on_change ]; then\n        $on_change\n",
+        r#"on_change=$PRJ_ON_CHANGE
+if [ -n "$on_change" ] && [ -f "$on_change" ] && [ -n "$PRJ_ON_CHANGE_SHA256" ]; then
+        # Hashed and run from a private copy, so the script can't change in between
+        on_change_copy=$(mktemp)
+        cat "$on_change" > "$on_change_copy"
+        chmod u+x "$on_change_copy"
+        [ "$({ sha256sum || shasum -a 256; } < "$on_change_copy" 2>/dev/null | cut -d' ' -f1)" = "$PRJ_ON_CHANGE_SHA256" ] && "$on_change_copy"
+        rm -f "$on_change_copy"
+"#,
+    ),
+];
+
+/// The layout version of the setup, `None` if wechsel wasn't set up yet
+pub fn layout_version(config_dir: &PathBuf) -> Result<Option<u32>, WechselError> {
+    let path = layout_version_path(config_dir);
+    match fs::read_to_string(&path) {
+        Ok(content) => content.trim().parse().map(Some).map_err(|_| {
+            WechselError::Invalid(format!(
+                "{path:?} doesn't contain a layout version: {content:?}"
+            ))
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(guess_layout_version(config_dir)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// Setups from before the version file
+fn guess_layout_version(config_dir: &Path) -> Option<u32> {
+    if get_old_config_file_path(config_dir).is_some() {
+        Some(0)
+    } else if get_project_tree(config_dir, false).is_ok() {
+        Some(1)
+    } else {
+        None
+    }
+}
+
+pub fn write_layout_version(version: u32, config_dir: &PathBuf) -> io::Result<()> {
+    fs::write(layout_version_path(config_dir), format!("{version}\n"))
+}
+
+pub fn pending_upgrades(config_dir: &PathBuf) -> Result<Vec<&'static Upgrade>, WechselError> {
+    let Some(version) = layout_version(config_dir)? else {
+        return Ok(vec![]);
+    };
+    Ok(UPGRADES
+        .iter()
+        .filter(|upgrade| upgrade.version > version)
+        .collect())
+}
+
+#[derive(Serialize)]
+pub struct AppliedUpgrade {
+    pub version: u32,
+    pub description: &'static str,
+}
+
+/// Runs the pending upgrades in order, the version is written after each one,
+/// so a failed upgrade continues where it stopped
+pub fn upgrade(
+    config_dir: &PathBuf,
+    no_prompts: bool,
+) -> Result<Vec<AppliedUpgrade>, WechselError> {
+    let pending = pending_upgrades(config_dir)?;
+    if pending.is_empty() {
+        say!("The wechsel setup is up to date");
+        return Ok(vec![]);
+    }
+
+    say!("The wechsel setup is from an older version of wechsel and needs these upgrades:");
+    for upgrade in pending.iter() {
+        say!("  {}: {}", upgrade.version, upgrade.description);
+    }
+    if !no_prompts
+        && !Confirm::new()
+            .with_prompt("Upgrade?")
+            .default(true)
+            .interact()
+            .map_err(|_| WechselError::Cancelled)?
+    {
+        return Err(WechselError::Cancelled);
+    }
+
+    let mut applied = vec![];
+    for upgrade in pending {
+        (upgrade.apply)(config_dir)?;
+        write_layout_version(upgrade.version, config_dir)?;
+        info!(
+            "Upgraded the wechsel setup to layout version {}: {}",
+            upgrade.version, upgrade.description
+        );
+        applied.push(AppliedUpgrade {
+            version: upgrade.version,
+            description: upgrade.description,
+        });
+    }
+    say!("Upgraded the wechsel setup to layout version {LAYOUT_VERSION}");
+    Ok(applied)
+}
+
+/// Offers the pending upgrades before a command runs, if there is a terminal to ask on
+pub fn check_layout(config_dir: &PathBuf) -> Result<(), WechselError> {
+    let Some(version) = layout_version(config_dir)? else {
+        return Ok(());
+    };
+    if version > LAYOUT_VERSION {
+        warn!(
+            "The wechsel setup has layout version {version}, but this wechsel only knows version {LAYOUT_VERSION}, consider updating wechsel"
+        );
+        return Ok(());
+    }
+    if version == LAYOUT_VERSION {
+        return Ok(());
+    }
+    if is_json() || !io::stdin().is_terminal() {
+        warn!("The wechsel setup is from an older version of wechsel, run `wechsel upgrade`");
+        return Ok(());
+    }
+    match upgrade(config_dir, false) {
+        Err(WechselError::Cancelled) => {
+            warn!("Continuing without upgrading, `wechsel upgrade` upgrades the setup later");
+            Ok(())
+        }
+        result => result.map(|_| ()),
+    }
+}
+
+fn migrate_projects_file(config_dir: &PathBuf) -> Result<(), WechselError> {
+    if get_old_config_file_path(config_dir).is_none() {
+        return Ok(());
+    }
+    let active = migrated_project(migrate(config_dir, true, false)?, config_dir)?;
+    switch_project(
+        find_project(&active, config_dir)?.as_ref(),
+        config_dir,
+        Navigation::Switch,
+        false,
+        ChangeOptions::default(),
+    )?;
+    Ok(())
+}
+
+fn use_allowed_scripts(config_dir: &PathBuf) -> Result<(), WechselError> {
+    let on_prj_change = load_config(config_dir)?
+        .hooks
+        .on_prj_change_script(config_dir);
+    for file in [bashrc_path(), fish_config_path(config_dir), on_prj_change] {
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        let mut fixed = content.clone();
+        for (unchecked, checked) in UNCHECKED_SCRIPTS {
+            fixed = fixed.replace(unchecked, checked);
+        }
+        if fixed != content {
+            fs::write(&file, &fixed)?;
+            info!("Only allowed project scripts run from {file:?} now");
+        }
+        // Edited snippets are left to the user
+        if fixed.contains("$PRJ_PATH/.init-prj") || fixed.contains("$PRJ_PATH/.on-prj-change") {
+            warn!(
+                "{file:?} still runs project scripts without checking them, use $PRJ_INIT, $PRJ_INIT_FISH and $PRJ_ON_CHANGE instead"
+            );
+        }
+    }
+    Ok(())
+}
+
+fn fix_env_file_name(config_dir: &PathBuf) -> Result<(), WechselError> {
+    for config in [bashrc_path(), fish_config_path(config_dir)] {
+        let Ok(content) = fs::read_to_string(&config) else {
+            continue;
+        };
+        if content.contains(MISSPELLED_ENV_FILE) {
+            fs::write(&config, content.replace(MISSPELLED_ENV_FILE, ENV_FILE))?;
+            info!("Replaced {MISSPELLED_ENV_FILE} with {ENV_FILE} in {config:?}");
+        }
+    }
+    Ok(())
+}
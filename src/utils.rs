@@ -1,14 +1,23 @@
 use std::{
-    fs::{self, DirEntry},
-    io::Error,
+    env,
     path::{Path, PathBuf},
 };
 
+use crate::config::load_config;
+use crate::file_system::FileSystem;
+use crate::workspace::Workspace;
+
 pub const PROJECT_EXTENSION: &str = "p";
 pub const WECHSEL_FOLDER_EXTENSION: &str = "w";
 
 pub const CURRENT_PROJECT_FOLDER: &str = "Project";
 pub const DEFAULT_ROOT_PRJ: &str = "home";
+pub const NO_AUTO_SWITCH_MARKER: &str = ".no-auto-switch";
+
+/// Overrides the home folder, for running wechsel somewhere else than in the real home folder
+pub const HOME_ENV: &str = "WECHSEL_HOME";
+/// Overrides the config dir
+pub const CONFIG_DIR_ENV: &str = "WECHSEL_CONFIG_DIR";
 
 pub const HOME_FOLDERS: [&str; 6] = [
     "Desktop",
@@ -21,7 +30,7 @@ pub const HOME_FOLDERS: [&str; 6] = [
 
 pub fn bashrc_path() -> PathBuf {
     path_from_iter([
-        dirs::home_dir().expect("No Home dir found"),
+        home_dir().expect("No Home dir found"),
         PathBuf::from(".bashrc"),
     ])
 }
@@ -40,7 +49,52 @@ pub fn get_environment_vars_path(config_dir: &PathBuf) -> PathBuf {
 pub fn get_environment_vars_fish_path(config_dir: &PathBuf) -> PathBuf {
     path_from_iter([config_dir, &PathBuf::from("environment_variables.fish")])
 }
+
+// The variables hooks exported for the active project, so they survive until the next change
+pub fn hook_env_path(config_dir: &PathBuf) -> PathBuf {
+    path_from_iter([config_dir, &PathBuf::from("hook_env.json")])
+}
+
+pub fn config_file_path(config_dir: &PathBuf) -> PathBuf {
+    path_from_iter([config_dir, &PathBuf::from("config.toml")])
+}
+
+pub fn layout_version_path(config_dir: &PathBuf) -> PathBuf {
+    path_from_iter([config_dir, &PathBuf::from("layout_version")])
+}
+
+pub fn lock_path(config_dir: &PathBuf) -> PathBuf {
+    path_from_iter([config_dir, &PathBuf::from("lock")])
+}
+
+pub fn history_path(config_dir: &PathBuf) -> PathBuf {
+    path_from_iter([config_dir, &PathBuf::from("history.json")])
+}
+
+pub fn trust_path(config_dir: &PathBuf) -> PathBuf {
+    path_from_iter([config_dir, &PathBuf::from("trusted.json")])
+}
+
+pub fn cd_hook_cache_path(config_dir: &Path) -> PathBuf {
+    path_from_iter([config_dir, &PathBuf::from("cd-hook-cache")])
+}
+fn env_dir(var: &str) -> Option<PathBuf> {
+    let dir = PathBuf::from(env::var_os(var).filter(|dir| !dir.is_empty())?);
+    if dir.is_absolute() {
+        Some(dir)
+    } else {
+        env::current_dir().ok().map(|cwd| cwd.join(dir))
+    }
+}
+
+/// The home folder wechsel works in, `$WECHSEL_HOME` or the home folder of the user
+pub fn home_dir() -> Option<PathBuf> {
+    env_dir(HOME_ENV).or_else(dirs::home_dir)
+}
+
 pub fn get_home_folder_paths<'a>() -> impl Iterator<Item = (&'a str, PathBuf)> {
+    // The xdg user dirs belong to the real home folder
+    let home = env_dir(HOME_ENV);
     [
         (HOME_FOLDERS[0], dirs::desktop_dir()),
         (HOME_FOLDERS[1], dirs::download_dir()),
@@ -50,61 +104,107 @@ pub fn get_home_folder_paths<'a>() -> impl Iterator<Item = (&'a str, PathBuf)> {
         (HOME_FOLDERS[5], dirs::audio_dir()),
     ]
     .into_iter()
-    .filter_map(|(name, path)| path.map(|a| (name, a)))
+    .filter_map(move |(name, path)| match &home {
+        Some(home) => Some((name, path_from_iter([home, &PathBuf::from(name)]))),
+        None => path.map(|a| (name, a)),
+    })
 }
 
+/// The folders of the home folder with the given names, the xdg user dirs are used where they are known
+pub fn home_folder_paths(names: &[String]) -> Vec<(String, PathBuf)> {
+    let home = home_dir().expect("Could not find home directory");
+    let xdg_paths: Vec<_> = get_home_folder_paths().collect();
+    names
+        .iter()
+        .map(|name| {
+            let path = xdg_paths
+                .iter()
+                .find(|(xdg_name, _)| xdg_name == name)
+                .map(|(_, path)| path.clone())
+                .unwrap_or(path_from_iter([&home, &PathBuf::from(name)]));
+            (name.clone(), path)
+        })
+        .collect()
+}
+
+/// The name of the link in the home folder that points to the active project
+pub fn current_project_folder(config_dir: &PathBuf) -> String {
+    load_config(config_dir)
+        .map(|config| config.general.current_project_folder)
+        .unwrap_or(CURRENT_PROJECT_FOLDER.to_string())
+}
+
+/// `$WECHSEL_CONFIG_DIR`, with `$WECHSEL_HOME` the `.config/wechsel` folder in it
 pub fn get_config_dir() -> Option<PathBuf> {
-    dirs::config_dir().map(|conf| PathBuf::from_iter([conf, PathBuf::from("wechsel")]))
+    if let Some(config_dir) = env_dir(CONFIG_DIR_ENV) {
+        return Some(config_dir);
+    }
+    match env_dir(HOME_ENV) {
+        Some(home) => Some(path_from_iter([home, PathBuf::from(".config/wechsel")])),
+        None => dirs::config_dir().map(|conf| PathBuf::from_iter([conf, PathBuf::from("wechsel")])),
+    }
+}
+
+/// With `$WECHSEL_HOME` the `.local/state/wechsel` folder in it
+pub fn get_state_dir() -> Option<PathBuf> {
+    if let Some(home) = env_dir(HOME_ENV) {
+        return Some(path_from_iter([
+            home,
+            PathBuf::from(".local/state/wechsel"),
+        ]));
+    }
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|state| PathBuf::from_iter([state, PathBuf::from("wechsel")]))
+}
+
+pub fn time_log_path(state_dir: &PathBuf) -> PathBuf {
+    path_from_iter([state_dir, &PathBuf::from("time_log.jsonl")])
+}
+
+pub fn log_file_path(state_dir: &PathBuf) -> PathBuf {
+    path_from_iter([state_dir, &PathBuf::from("wechsel.log")])
 }
 
 //** Find subfolders of target path that have the wechsel extension*/
-pub fn get_folders(path: &PathBuf) -> Vec<PathBuf> {
-    fs::read_dir(path)
-        .ok()
+pub fn get_folders(fs: &dyn FileSystem, path: &Path) -> Vec<PathBuf> {
+    fs.read_dir(path)
         .map(|children| {
             children
                 .into_iter()
-                .filter_map(|file| {
-                    is_entry_folder_with_extension(&file, WECHSEL_FOLDER_EXTENSION)
-                        .map(|file| file.path())
-                })
+                .filter(|child| is_folder_with_extension(fs, child, WECHSEL_FOLDER_EXTENSION))
                 .collect()
         })
         .unwrap_or_default()
 }
 
+//** Walk up from path to the nearest folder with the project extension*/
+pub fn find_project_folder(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|ancestor| {
+            ancestor
+                .extension()
+                .map(|ext| ext == PROJECT_EXTENSION)
+                .unwrap_or(false)
+        })
+        .map(Path::to_path_buf)
+}
+
 pub fn path_from_iter<const N: usize, S: AsRef<Path>>(inp: [S; N]) -> PathBuf {
     inp.into_iter().collect()
 }
 
-pub fn is_entry_folder_with_extension<'a>(
-    entry: &'a Result<DirEntry, Error>,
-    extension: &str,
-) -> Option<&'a DirEntry> {
-    entry.as_ref().ok().and_then(|entry| {
-        (entry.path().is_dir()
-            && entry
-                .path()
-                .extension()
-                .map(|ext| ext == extension)
-                .unwrap_or(false))
-        .then_some(entry)
-    })
+pub fn is_folder_with_extension(fs: &dyn FileSystem, path: &Path, extension: &str) -> bool {
+    path.extension()
+        .map(|ext| ext == extension)
+        .unwrap_or(false)
+        && fs.is_dir(path)
 }
 
-pub fn query_active_project() -> Option<String> {
-    let project_folder_path = path_from_iter([
-        dirs::home_dir().expect("Could not find home directory"),
-        PathBuf::from("Project"),
-    ]);
+pub fn query_active_project(config_dir: &Path) -> Option<String> {
+    Workspace::with_config_dir(config_dir).ok()?.active()
+}
 
-    if project_folder_path.is_symlink() {
-        if let Ok(target) = fs::read_link(project_folder_path) {
-            return target
-                .file_stem()
-                .and_then(|stem| stem.to_str())
-                .map(|a| a.to_string());
-        }
-    }
-    None
+pub fn query_active_project_path(config_dir: &Path) -> Option<PathBuf> {
+    Workspace::with_config_dir(config_dir).ok()?.active_path()
 }
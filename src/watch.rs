@@ -0,0 +1,351 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use log::warn;
+use serde::Serialize;
+
+use crate::output::is_json;
+use wechsel::{
+    PROJECT_EXTENSION, ProjectTreeNode, WECHSEL_FOLDER_EXTENSION, WechselError,
+    current_project_folder, get_project_tree, home_dir, query_active_project,
+    query_active_project_path,
+};
+
+// Events that arrive within this time of each other are handled together
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WatchEvent {
+    ProjectCreated {
+        name: String,
+        path: PathBuf,
+    },
+    ProjectRemoved {
+        name: String,
+        path: PathBuf,
+    },
+    ProjectRenamed {
+        old_name: String,
+        old_path: PathBuf,
+        name: String,
+        path: PathBuf,
+    },
+    FolderAdded {
+        name: String,
+        path: PathBuf,
+        folder: String,
+    },
+    FolderRemoved {
+        name: String,
+        path: PathBuf,
+        folder: String,
+    },
+    ActiveChanged {
+        name: String,
+        path: PathBuf,
+        old_name: String,
+        old_path: PathBuf,
+    },
+}
+
+impl WatchEvent {
+    fn describe(&self) -> String {
+        match self {
+            WatchEvent::ProjectCreated { name, path } => {
+                format!("Project {name} created at {path:?}")
+            }
+            WatchEvent::ProjectRemoved { name, path } => {
+                format!("Project {name} at {path:?} removed")
+            }
+            WatchEvent::ProjectRenamed {
+                old_name,
+                old_path,
+                name,
+                path,
+            } => format!("Project {old_name} at {old_path:?} renamed to {name} at {path:?}"),
+            WatchEvent::FolderAdded { name, folder, .. } => {
+                format!("Folder {folder} added to project {name}")
+            }
+            WatchEvent::FolderRemoved { name, folder, .. } => {
+                format!("Folder {folder} removed from project {name}")
+            }
+            WatchEvent::ActiveChanged { name, old_name, .. } => {
+                format!("Active project changed from {old_name} to {name}")
+            }
+        }
+    }
+}
+
+struct Snapshot {
+    // project path -> (name, folders)
+    projects: BTreeMap<PathBuf, (String, Vec<String>)>,
+    active: String,
+    active_path: PathBuf,
+}
+
+impl Snapshot {
+    fn take(config_dir: &Path) -> Result<Snapshot, WechselError> {
+        fn flatten(node: ProjectTreeNode, projects: &mut BTreeMap<PathBuf, (String, Vec<String>)>) {
+            projects.insert(node.path, (node.prj_name, node.folders.unwrap_or_default()));
+            for child in node.children {
+                flatten(child, projects);
+            }
+        }
+        let mut projects = BTreeMap::new();
+        flatten(get_project_tree(config_dir, true)?, &mut projects);
+        Ok(Snapshot {
+            projects,
+            active: query_active_project(config_dir).unwrap_or_default(),
+            active_path: query_active_project_path(config_dir).unwrap_or_default(),
+        })
+    }
+}
+
+/// Prints an event for every change of the project tree or the active project until killed,
+/// with `--json` one json object per line
+pub fn watch(config_dir: &Path) -> Result<(), WechselError> {
+    let mut watcher = TreeWatcher::new(config_dir)?;
+    loop {
+        for event in watcher.next_events()? {
+            if is_json() {
+                println!("{}", serde_json::to_string(&event).unwrap_or_default());
+            } else {
+                println!("{}", event.describe());
+            }
+        }
+    }
+}
+
+/// Watches the project folders and the link to the active project with inotify
+pub struct TreeWatcher {
+    config_dir: PathBuf,
+    home: PathBuf,
+    current_project_folder: String,
+    inotify: Inotify,
+    watches: HashMap<PathBuf, WatchDescriptor>,
+    snapshot: Snapshot,
+    buffer: [u8; 4096],
+}
+
+impl TreeWatcher {
+    pub fn new(config_dir: &Path) -> Result<TreeWatcher, WechselError> {
+        let config_dir = config_dir.to_path_buf();
+        let mut watcher = TreeWatcher {
+            home: home_dir().ok_or(io::Error::other("No Home dir found"))?,
+            current_project_folder: current_project_folder(&config_dir),
+            inotify: Inotify::init()?,
+            watches: HashMap::new(),
+            snapshot: Snapshot::take(&config_dir)?,
+            buffer: [0; 4096],
+            config_dir,
+        };
+        watcher.update_watches();
+        Ok(watcher)
+    }
+
+    /// Blocks until the tree or the active project changed and returns what changed
+    pub fn next_events(&mut self) -> Result<Vec<WatchEvent>, WechselError> {
+        loop {
+            // Old path -> new path of everything that got moved
+            let mut moves: Vec<(PathBuf, PathBuf)> = vec![];
+            let mut moved_from: HashMap<u32, PathBuf> = HashMap::new();
+            let mut relevant = false;
+
+            let mut events: Vec<_> = self
+                .inotify
+                .read_events_blocking(&mut self.buffer)?
+                .map(|event| event.to_owned())
+                .collect();
+            loop {
+                for event in events.drain(..) {
+                    let Some(name) = event.name.as_ref() else {
+                        continue;
+                    };
+                    let name = Path::new(name);
+                    if !is_relevant(name, &self.current_project_folder) {
+                        continue;
+                    }
+                    relevant = true;
+
+                    let Some(dir) = self
+                        .watches
+                        .iter()
+                        .find(|(_, wd)| **wd == event.wd)
+                        .map(|(dir, _)| dir)
+                    else {
+                        continue;
+                    };
+                    let path = dir.join(name);
+                    if event.mask.contains(EventMask::MOVED_FROM) {
+                        moved_from.insert(event.cookie, path);
+                    } else if event.mask.contains(EventMask::MOVED_TO) {
+                        if let Some(old) = moved_from.remove(&event.cookie) {
+                            moves.push((old, path));
+                        }
+                    }
+                }
+
+                thread::sleep(DEBOUNCE);
+                match self.inotify.read_events(&mut self.buffer) {
+                    Ok(new_events) => events.extend(new_events.map(|event| event.to_owned())),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(e) => return Err(e.into()),
+                }
+                if events.is_empty() {
+                    break;
+                }
+            }
+
+            if !relevant {
+                continue;
+            }
+
+            // The tree can be in the middle of a change, the next event takes a new snapshot
+            let snapshot = match Snapshot::take(&self.config_dir) {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    warn!("Could not read the project tree: {e}");
+                    continue;
+                }
+            };
+            let events = diff(&self.snapshot, &snapshot, &moves);
+            self.snapshot = snapshot;
+            self.update_watches();
+            if !events.is_empty() {
+                return Ok(events);
+            }
+        }
+    }
+
+    fn update_watches(&mut self) {
+        let mask = WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO
+            | WatchMask::ONLYDIR;
+
+        let wanted: Vec<&PathBuf> = std::iter::once(&self.home)
+            .chain(self.snapshot.projects.keys())
+            .collect();
+
+        let inotify = &mut self.inotify;
+        self.watches.retain(|path, wd| {
+            let keep = wanted.contains(&path);
+            if !keep {
+                let _ = inotify.watches().remove(wd.clone());
+            }
+            keep
+        });
+        for path in wanted {
+            if !self.watches.contains_key(path) {
+                // Folders can vanish between taking the snapshot and adding the watch
+                if let Ok(wd) = self.inotify.watches().add(path, mask) {
+                    self.watches.insert(path.clone(), wd);
+                }
+            }
+        }
+    }
+}
+
+// Only project folders, wechsel folders and the ~/Project link can change the tree or the active project
+fn is_relevant(name: &Path, current_project_folder: &str) -> bool {
+    name == Path::new(current_project_folder)
+        || name
+            .extension()
+            .map(|ext| ext == PROJECT_EXTENSION || ext == WECHSEL_FOLDER_EXTENSION)
+            .unwrap_or_default()
+}
+
+fn diff(old: &Snapshot, new: &Snapshot, moves: &[(PathBuf, PathBuf)]) -> Vec<WatchEvent> {
+    let mut events = vec![];
+    let mut created: Vec<&PathBuf> = new
+        .projects
+        .keys()
+        .filter(|path| !old.projects.contains_key(*path))
+        .collect();
+
+    for (path, (name, folders)) in old.projects.iter() {
+        if let Some((_, new_folders)) = new.projects.get(path) {
+            diff_folders(name, path, folders, new_folders, &mut events);
+            continue;
+        }
+
+        // A project whose folder, or the folder of an ancestor, got moved
+        let moved_to = moves.iter().find_map(|(from, to)| {
+            path.strip_prefix(from)
+                .ok()
+                .map(|rest| {
+                    if rest.as_os_str().is_empty() {
+                        to.clone()
+                    } else {
+                        to.join(rest)
+                    }
+                })
+                .filter(|moved| created.contains(&moved))
+        });
+        match moved_to {
+            Some(moved) => {
+                created.retain(|path| **path != moved);
+                let (new_name, new_folders) = &new.projects[&moved];
+                events.push(WatchEvent::ProjectRenamed {
+                    old_name: name.clone(),
+                    old_path: path.clone(),
+                    name: new_name.clone(),
+                    path: moved.clone(),
+                });
+                diff_folders(new_name, &moved, folders, new_folders, &mut events);
+            }
+            None => events.push(WatchEvent::ProjectRemoved {
+                name: name.clone(),
+                path: path.clone(),
+            }),
+        }
+    }
+
+    for path in created {
+        events.push(WatchEvent::ProjectCreated {
+            name: new.projects[path].0.clone(),
+            path: path.clone(),
+        });
+    }
+
+    if old.active != new.active || old.active_path != new.active_path {
+        events.push(WatchEvent::ActiveChanged {
+            name: new.active.clone(),
+            path: new.active_path.clone(),
+            old_name: old.active.clone(),
+            old_path: old.active_path.clone(),
+        });
+    }
+    events
+}
+
+fn diff_folders(
+    name: &str,
+    path: &Path,
+    old: &[String],
+    new: &[String],
+    events: &mut Vec<WatchEvent>,
+) {
+    for folder in new.iter().filter(|folder| !old.contains(folder)) {
+        events.push(WatchEvent::FolderAdded {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            folder: folder.clone(),
+        });
+    }
+    for folder in old.iter().filter(|folder| !new.contains(folder)) {
+        events.push(WatchEvent::FolderRemoved {
+            name: name.to_string(),
+            path: path.to_path_buf(),
+            folder: folder.clone(),
+        });
+    }
+}
@@ -0,0 +1,815 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use log::{debug, info, warn};
+use serde::Serialize;
+
+use crate::config::{Config, check_name, conflict_strategy, load_config};
+use crate::conflict::{ConflictStrategy, resolve_conflict};
+use crate::error::WechselError;
+use crate::file_system::{FileSystem, OsFileSystem};
+use crate::history::History;
+use crate::hooks::{
+    HookEvent, HookOutputSink, HookProject, HookRun, HookRunner, LifecycleEvent, LinkAction,
+    LinkChange, ON_ENTER_HOOK, ON_LEAVE_HOOK, Transition, chain_names, lifecycle_script,
+    project_chain,
+};
+use crate::lock::ProjectLock;
+use crate::time_log::{TimeEvent, append_time_event, now};
+use crate::tree::{
+    FoundProject, ProjectTreeNode, find_all_projects, find_project_by_path, project_tree,
+};
+use crate::trust::{
+    FISH_INIT_SCRIPT, INIT_SCRIPT, PROJECT_ON_CHANGE_SCRIPT, TrustStore, project_scripts,
+};
+use crate::utils::{
+    PROJECT_EXTENSION, WECHSEL_FOLDER_EXTENSION, current_project_folder, get_config_dir,
+    get_environment_vars_fish_path, get_environment_vars_path, get_folders, home_dir,
+    hook_env_path, path_from_iter,
+};
+
+/// A home folder with its projects and the config dir that belongs to it.
+/// Nothing in here prints or prompts, the command line interface is built on top of it.
+#[derive(Clone, Debug)]
+pub struct Workspace {
+    home: PathBuf,
+    config_dir: PathBuf,
+    fs: Rc<dyn FileSystem>,
+    hook_output: HookOutputSink,
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct ChangeOptions {
+    /// Overrides the conflict strategy of the config file
+    pub conflict: Option<ConflictStrategy>,
+    /// Don't run the pre-change hooks, so nothing can block the change
+    pub force: bool,
+}
+
+#[derive(Serialize)]
+pub struct ChangeResult {
+    pub project: String,
+    pub path: PathBuf,
+    pub old_project: String,
+    /// Every hook that ran, in order
+    pub hooks: Vec<HookRun>,
+    /// The variables hooks of the JSON protocol exported
+    pub env: BTreeMap<String, String>,
+    /// The messages of hooks of the JSON protocol
+    pub messages: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct RenameResult {
+    pub project: String,
+    pub path: PathBuf,
+    pub old_project: String,
+}
+
+/// A project script with the hash it was allowed with
+#[derive(Serialize)]
+pub struct AllowedScript {
+    pub script: PathBuf,
+    pub hash: String,
+}
+
+impl Workspace {
+    pub fn new(home: impl Into<PathBuf>, config_dir: impl Into<PathBuf>) -> Workspace {
+        Workspace {
+            home: home.into(),
+            config_dir: config_dir.into(),
+            fs: Rc::new(OsFileSystem),
+            hook_output: HookOutputSink::default(),
+        }
+    }
+
+    /// Works on `fs` instead of the real filesystem, e.g. a [`MemoryFileSystem`](crate::MemoryFileSystem)
+    pub fn with_file_system(mut self, fs: Rc<dyn FileSystem>) -> Workspace {
+        self.fs = fs;
+        self
+    }
+
+    /// Shows the output of the hooks through `output` while they run, without it nothing is shown
+    pub fn with_hook_output(mut self, output: HookOutputSink) -> Workspace {
+        self.hook_output = output;
+        self
+    }
+
+    /// The home folder and config dir the `wechsel` command uses, see `WECHSEL_HOME` and `WECHSEL_CONFIG_DIR`
+    pub fn from_env() -> Result<Workspace, WechselError> {
+        let config_dir = get_config_dir().ok_or(io::Error::other("No config folder found"))?;
+        Workspace::with_config_dir(&config_dir)
+    }
+
+    /// The home folder of the environment together with `config_dir`
+    pub fn with_config_dir(config_dir: &Path) -> Result<Workspace, WechselError> {
+        let home = home_dir().ok_or(io::Error::other("No Home dir found"))?;
+        Ok(Workspace::new(home, config_dir))
+    }
+
+    pub fn home(&self) -> &Path {
+        &self.home
+    }
+
+    pub fn config_dir(&self) -> &PathBuf {
+        &self.config_dir
+    }
+
+    pub fn config(&self) -> Result<Config, WechselError> {
+        load_config(&self.config_dir)
+    }
+
+    pub fn tree(&self, collect_folders: bool) -> Result<ProjectTreeNode, WechselError> {
+        project_tree(&*self.fs, &self.home, &self.config_dir, collect_folders)
+    }
+
+    /// The link in the home folder that points to the active project
+    pub fn current_project_link(&self) -> PathBuf {
+        path_from_iter([
+            &self.home,
+            &PathBuf::from(current_project_folder(&self.config_dir)),
+        ])
+    }
+
+    pub fn active(&self) -> Option<String> {
+        self.active_path().and_then(|target| {
+            target
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(|a| a.to_string())
+        })
+    }
+
+    pub fn active_path(&self) -> Option<PathBuf> {
+        let project_folder_path = self.current_project_link();
+
+        if self.fs.is_symlink(&project_folder_path) {
+            return self.fs.read_link(&project_folder_path).ok();
+        }
+        None
+    }
+
+    /// One of the projects with each name, `None` for names without a project
+    pub fn search<const N: usize>(
+        &self,
+        targets: [&str; N],
+    ) -> Result<[Option<Rc<FoundProject>>; N], WechselError> {
+        Ok(
+            find_all_projects(targets, &*self.fs, &self.home, &self.config_dir)?
+                .map(|mut matches| matches.pop()),
+        )
+    }
+
+    /// Finds the single project with the given name
+    pub fn find(&self, name: &str) -> Result<Rc<FoundProject>, WechselError> {
+        let [mut matches] = find_all_projects([name], &*self.fs, &self.home, &self.config_dir)?;
+        match matches.len() {
+            0 => Err(WechselError::NotFound(format!(
+                "Could not find Project {name}"
+            ))),
+            1 => Ok(matches.remove(0)),
+            _ => Err(WechselError::Ambiguous(
+                name.to_string(),
+                matches.iter().map(|found| found.path.clone()).collect(),
+            )),
+        }
+    }
+
+    /// The project with the folder `path`, names aren't unique but paths are
+    pub fn find_by_path(&self, path: &Path) -> Result<Option<Rc<FoundProject>>, WechselError> {
+        find_project_by_path(path, &*self.fs, &self.home, &self.config_dir)
+    }
+
+    pub fn active_project(&self) -> Option<Rc<FoundProject>> {
+        self.find_by_path(&self.active_path()?).ok().flatten()
+    }
+
+    // The project with the folder `path`, for the methods that take one
+    fn project_at(&self, path: &Path) -> Result<Rc<FoundProject>, WechselError> {
+        self.find_by_path(path)?
+            .ok_or(WechselError::NotFound(format!(
+                "There is no project at {path:?}"
+            )))
+    }
+
+    pub fn link_folder(
+        &self,
+        path: &PathBuf,
+        target_name: &str,
+        conflict: ConflictStrategy,
+    ) -> io::Result<bool> {
+        let target = path_from_iter([&self.home, &PathBuf::from(target_name)]);
+        let fs = &self.fs;
+        if !fs.exists(path) {
+            warn!("Could not symlink folder ({path:?}) because it doesn't exists",);
+            return Ok(false);
+        }
+        if fs.exists(&target)
+            && !fs.is_symlink(&target)
+            && !resolve_conflict(fs.as_ref(), &target, path, conflict)?
+        {
+            warn!(
+                "Could not symlink folder ({path:?}): {target:?} exists and is not a symlink. Did you already initialize wechsel on your system? Calling `wechsel init` might resolve this issue, `--on-conflict merge` or `--on-conflict stash` replace it.",
+            );
+            return Ok(false);
+        }
+
+        if fs.is_symlink(&target) {
+            fs.remove_file(&target)?;
+            debug!("Removed link {target:?}");
+        }
+
+        match fs.symlink(path, &target) {
+            Ok(()) => info!("Linked {target:?} to {path:?}"),
+            Err(e) => warn!("Could not link {target:?} to {path:?}: {e}"),
+        }
+
+        Ok(true)
+    }
+
+    /// The wechsel folders that get linked into the home folder for a project, by name.
+    /// Folders of child projects take precedence over the ones of their ancestors.
+    pub fn project_links(&self, prj: &Rc<FoundProject>) -> Vec<(String, PathBuf)> {
+        let mut links: Vec<(String, PathBuf)> = vec![];
+        let mut prj = Some(prj.clone());
+        while let Some(p) = prj {
+            for path in get_folders(&*self.fs, &p.path) {
+                let Some(clean_name) = path
+                    .file_stem()
+                    .unwrap()
+                    .to_str()
+                    .map(|name| name.to_string())
+                else {
+                    continue;
+                };
+                if links.iter().any(|(name, _)| name == &clean_name) {
+                    debug!(
+                        "Skipping {path:?}, {clean_name} is already linked from a child project"
+                    );
+                    continue;
+                }
+                links.push((clean_name, path));
+            }
+            prj = p.parent.clone();
+        }
+        links
+    }
+
+    /// Links ~/Project and the wechsel folders of the project into the home folder,
+    /// `conflict` decides what happens to real folders in the way of the wechsel folders.
+    /// Returns the links that were removed and created.
+    pub fn link_project(
+        &self,
+        prj: &Rc<FoundProject>,
+        conflict: ConflictStrategy,
+    ) -> io::Result<Vec<LinkChange>> {
+        let mut changes = vec![];
+        // A real ~/Project is never touched, there is no project it could belong to
+        self.link_and_record(
+            &prj.path,
+            &current_project_folder(&self.config_dir),
+            ConflictStrategy::Skip,
+            &mut changes,
+        )?;
+        for (name, path) in self.project_links(prj) {
+            self.link_and_record(&path, &name, conflict, &mut changes)?;
+        }
+        Ok(changes)
+    }
+
+    // Links a folder like link_folder and adds what changed to `changes`
+    fn link_and_record(
+        &self,
+        path: &PathBuf,
+        target_name: &str,
+        conflict: ConflictStrategy,
+        changes: &mut Vec<LinkChange>,
+    ) -> io::Result<()> {
+        let link = path_from_iter([&self.home, &PathBuf::from(target_name)]);
+        let source = || match self.fs.is_symlink(&link) {
+            true => self.fs.read_link(&link).ok(),
+            false => None,
+        };
+        let before = source();
+        self.link_folder(path, target_name, conflict)?;
+        let after = source();
+        if before == after {
+            return Ok(());
+        }
+        for (action, source) in [(LinkAction::Removed, before), (LinkAction::Created, after)] {
+            if let Some(source) = source {
+                changes.push(LinkChange {
+                    action,
+                    link: link.clone(),
+                    source,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Makes `prj_name` the active project and runs the hooks.
+    /// The pre-change hooks can block the change before anything is touched,
+    /// the on-leave hooks run before the links are changed, the on-enter hooks and on-prj-change after.
+    pub fn change(
+        &self,
+        prj_name: &str,
+        options: ChangeOptions,
+    ) -> Result<ChangeResult, WechselError> {
+        self.change_at(&self.find(prj_name)?.path, options)
+    }
+
+    /// Like [`change`](Workspace::change) for the project with the folder `path`, names aren't unique but paths are
+    pub fn change_at(
+        &self,
+        path: &Path,
+        options: ChangeOptions,
+    ) -> Result<ChangeResult, WechselError> {
+        let config_dir = &self.config_dir;
+        let conflict = conflict_strategy(options.conflict, config_dir)?;
+        let _lock = ProjectLock::acquire(config_dir)?;
+
+        let active = self.active().unwrap_or_default();
+
+        let prj_path = self.project_at(path)?;
+        let prj_name = prj_path.name.as_str();
+        let old_prj_path = self.active_project();
+
+        let old_chain = old_prj_path.as_ref().map(project_chain).unwrap_or_default();
+        let new_chain = project_chain(&prj_path);
+        let transition = Transition::new(&old_chain, &new_chain);
+
+        let prj_path_string = prj_path.path.to_str().unwrap_or_default().to_string();
+
+        let mut env_vars: HashMap<String, String> = HashMap::from_iter(vec![
+            ("PRJ".to_owned(), prj_name.to_owned()),
+            ("PRJ_PATH".to_owned(), prj_path_string.clone()),
+            ("OLD_PRJ".to_owned(), active.clone()),
+            ("OLD_CHAIN".to_owned(), chain_names(&old_chain)),
+            ("NEW_CHAIN".to_owned(), chain_names(&new_chain)),
+        ]);
+
+        if let Some(old_prj_path) = &old_prj_path {
+            let old_prj_path = old_prj_path.path.to_str().unwrap_or_default().to_string();
+            env_vars.insert("OLD_PRJ_PATH".to_owned(), old_prj_path);
+        }
+
+        // What hooks of the JSON protocol get, the event is set for each kind of hook
+        let mut event = HookEvent {
+            project: Some((&prj_path).into()),
+            old_project: old_prj_path.as_ref().map(HookProject::from),
+            old_chain: old_chain.iter().map(HookProject::from).collect(),
+            new_chain: new_chain.iter().map(HookProject::from).collect(),
+            ..HookEvent::new("pre-change")
+        };
+
+        // A failing hook doesn't stop the change, it is reported at the end
+        let hooks = self.config()?.hooks;
+        let fs = &*self.fs;
+        let mut runner = HookRunner::new(&hooks, config_dir, self.fs.clone())
+            .with_output(self.hook_output.clone());
+        let trust = TrustStore::load(fs, config_dir);
+        // The runner warns about the hooks it skips, these only run from the shell and on-prj-change
+        for name in [PROJECT_ON_CHANGE_SCRIPT, INIT_SCRIPT, FISH_INIT_SCRIPT] {
+            let script = path_from_iter([&prj_path.path, &PathBuf::from(name)]);
+            if fs.is_file(&script) && !trust.is_allowed(fs, &script) {
+                warn!(
+                    "{script:?} changed or was never allowed, it doesn't run until `wechsel allow {prj_name}`"
+                );
+            }
+        }
+        // The default on-prj-change script only runs the one of the project if it is set and has the hash
+        let on_change = path_from_iter([&prj_path.path, &PathBuf::from(PROJECT_ON_CHANGE_SCRIPT)]);
+        if let Some(hash) = trust.allowed_hash(fs, &on_change) {
+            env_vars.insert("PRJ_ON_CHANGE_SHA256".to_owned(), hash.to_owned());
+            let on_change = on_change.to_str().unwrap_or_default().to_owned();
+            env_vars.insert("PRJ_ON_CHANGE".to_owned(), on_change);
+        }
+        if hooks.enabled && !options.force {
+            let pre_change = hooks.pre_change_script(config_dir);
+            runner.run_veto_hooks(&pre_change, &event, &env_vars, None)?;
+            runner.run_pre_change_hooks(&transition.leaving, &event, &env_vars)?;
+        } else if options.force {
+            info!("Skipping the pre-change hooks");
+        }
+
+        let mut hook_result = Ok(());
+        if hooks.enabled {
+            event.event = "on-leave".to_string();
+            hook_result =
+                runner.run_project_hooks(ON_LEAVE_HOOK, &transition.leaving, &event, &env_vars);
+        }
+
+        event.links = self.link_project(&prj_path, conflict)?;
+        write_env_files(fs, prj_name, &prj_path_string, runner.env(), config_dir)?;
+
+        if hooks.enabled {
+            event.event = "on-enter".to_string();
+            hook_result = hook_result.and(runner.run_project_hooks(
+                ON_ENTER_HOOK,
+                &transition.entering,
+                &event,
+                &env_vars,
+            ));
+
+            // Global on change script .config/on-prj-change and the ones in on-prj-change.d
+            event.event = "on-prj-change".to_string();
+            let on_change = hooks.on_prj_change_script(config_dir);
+            hook_result = hook_result.and(runner.run_hooks(&on_change, &event, &env_vars, None));
+
+            // Later hooks can have asked for more variables
+            if !runner.env().is_empty() {
+                write_env_files(fs, prj_name, &prj_path_string, runner.env(), config_dir)?;
+            }
+        }
+        let env = runner.env().clone();
+        let messages = runner.messages().to_vec();
+        hook_result.map(|_| ChangeResult {
+            project: prj_name.to_string(),
+            path: prj_path.path.clone(),
+            old_project: active,
+            hooks: runner.into_runs(),
+            env,
+            messages,
+        })
+    }
+
+    /// Creates the project `prj_name` with the given wechsel folders as a child of `parent`
+    /// and runs the on-prj-create script in it. An existing project folder is reused.
+    pub fn create(
+        &self,
+        prj_name: &str,
+        parent: &str,
+        folders: &[String],
+    ) -> Result<PathBuf, WechselError> {
+        //get parent path
+        let [parent_path] = self.search([parent])?;
+        let parent_path = &parent_path
+            .as_ref()
+            .ok_or_else(|| {
+                WechselError::NotFound(format!("The parent project {parent} could not be found"))
+            })?
+            .path;
+        self.create_in(prj_name, parent_path, folders)
+    }
+
+    /// Like [`create`](Workspace::create) with the folder of the parent project
+    pub fn create_in(
+        &self,
+        prj_name: &str,
+        parent_path: &Path,
+        folders: &[String],
+    ) -> Result<PathBuf, WechselError> {
+        check_name("The project name", prj_name).map_err(WechselError::Invalid)?;
+        for folder in folders {
+            check_name("The folder name", folder).map_err(WechselError::Invalid)?;
+        }
+        let config_dir = &self.config_dir;
+        let _lock = ProjectLock::acquire(config_dir)?;
+        let parent_path = &self.project_at(parent_path)?.path;
+
+        let mut new_pr_path = path_from_iter([parent_path, &PathBuf::from(prj_name)])
+            .with_extension(PROJECT_EXTENSION);
+
+        // Create Project Folder
+        let fs = &self.fs;
+        if !fs.exists(&new_pr_path) {
+            fs.create_dir_all(&new_pr_path)?;
+            info!("Created project folder {new_pr_path:?}");
+        } else if !fs.is_dir(&new_pr_path) {
+            return Err(WechselError::Conflict(format!(
+                "A file with the name of the new project exists in the place the project folder should be placed. Please either remove that file or specify a different name. {new_pr_path:?}"
+            )));
+        }
+
+        // Create Subfolders
+        for subfolder in folders.iter() {
+            new_pr_path.push(PathBuf::from(subfolder).with_extension(WECHSEL_FOLDER_EXTENSION));
+
+            if !fs.exists(&new_pr_path) {
+                fs.create_dir_all(&new_pr_path)?;
+                info!("Created folder {new_pr_path:?}");
+            } else {
+                debug!("Folder {new_pr_path:?} already exists");
+            }
+
+            new_pr_path.pop();
+        }
+
+        // Call on create script and the ones in on-prj-create.d
+        let hooks = self.config()?.hooks;
+        if hooks.enabled {
+            let script = hooks.on_prj_create_script(config_dir);
+            let created = self.find_by_path(&new_pr_path)?;
+            let event = HookEvent {
+                project: Some(HookProject {
+                    name: prj_name.to_string(),
+                    path: new_pr_path.clone(),
+                }),
+                new_chain: created
+                    .as_ref()
+                    .map(|created| {
+                        project_chain(created)
+                            .iter()
+                            .map(HookProject::from)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                ..HookEvent::new("on-prj-create")
+            };
+            let env_vars: HashMap<String, String> = HashMap::from_iter(vec![
+                ("PRJ".to_owned(), prj_name.to_owned()),
+                (
+                    "PRJ_PATH".to_owned(),
+                    new_pr_path.to_str().unwrap_or_default().to_owned(),
+                ),
+            ]);
+            HookRunner::new(&hooks, config_dir, self.fs.clone())
+                .with_output(self.hook_output.clone())
+                .run_hooks(&script, &event, &env_vars, Some(&new_pr_path))?;
+        }
+        Ok(new_pr_path)
+    }
+
+    /// Renames the folder of a project, the links follow if the active project is inside of it.
+    /// The history and the time log know the project by its new name afterwards.
+    /// A failing on-prj-rename hook doesn't undo the rename, it is returned as `HookFailed` afterwards
+    pub fn rename(&self, prj_name: &str, new_name: &str) -> Result<RenameResult, WechselError> {
+        self.rename_at(&self.find(prj_name)?.path, new_name)
+    }
+
+    /// Like [`rename`](Workspace::rename) for the project with the folder `path`
+    pub fn rename_at(&self, path: &Path, new_name: &str) -> Result<RenameResult, WechselError> {
+        check_name("The new project name", new_name).map_err(WechselError::Invalid)?;
+        let _lock = ProjectLock::acquire(&self.config_dir)?;
+
+        let prj = self.project_at(path)?;
+        let prj_name = prj.name.as_str();
+        if prj.path == self.home {
+            return Err(WechselError::Invalid(format!(
+                "{prj_name} is the home folder itself, general.root_project in the config file sets its name"
+            )));
+        }
+        match self.find(new_name) {
+            Err(WechselError::NotFound(_)) => {}
+            Err(e) => return Err(e),
+            Ok(_) => {
+                return Err(WechselError::Conflict(format!(
+                    "There already is a project named {new_name}"
+                )));
+            }
+        }
+        let new_path = path_from_iter([
+            prj.path.parent().unwrap_or(&self.home),
+            Path::new(&format!("{new_name}.{PROJECT_EXTENSION}")),
+        ]);
+        if self.fs.exists(&new_path) {
+            return Err(WechselError::Conflict(format!(
+                "Can't rename {prj_name}, {new_path:?} already exists"
+            )));
+        }
+
+        let active_path = self.active_path();
+        self.fs.rename(&prj.path, &new_path)?;
+        info!("Renamed {:?} to {new_path:?}", prj.path);
+
+        // Allowed scripts stay allowed in their new place
+        let mut trust = TrustStore::load(&*self.fs, &self.config_dir);
+        if trust.moved(&prj.path, &new_path) {
+            trust.save(&*self.fs, &self.config_dir)?;
+        }
+
+        // Where something that was in the old folder is now
+        let moved = |path: &Path| {
+            path.strip_prefix(&prj.path)
+                .ok()
+                .map(|rest| match rest.as_os_str().is_empty() {
+                    true => new_path.clone(),
+                    false => new_path.join(rest),
+                })
+        };
+
+        // The links into the old folder are dangling now
+        if let Some(active_path) = active_path {
+            if let Some(active_path) = moved(&active_path) {
+                let active = self
+                    .find_by_path(&active_path)?
+                    .ok_or(WechselError::NotFound(format!(
+                        "Could not find the active project at {active_path:?}"
+                    )))?;
+                self.link_project(&active, ConflictStrategy::Skip)?;
+                // Variables of the hooks that point into the old folder follow it
+                let env = read_hook_env(&*self.fs, &self.config_dir)
+                    .into_iter()
+                    .map(|(name, value)| match moved(Path::new(&value)) {
+                        Some(path) => (name, path.to_string_lossy().into_owned()),
+                        None => (name, value),
+                    })
+                    .collect();
+                write_env_files(
+                    &*self.fs,
+                    &active.name,
+                    active_path.to_str().unwrap_or_default(),
+                    &env,
+                    &self.config_dir,
+                )?;
+            }
+        }
+
+        // Back and forward find the project by its new name and its tracked time counts for it
+        let mut history = History::load(&*self.fs, &self.config_dir);
+        if history.rename(&prj.name, new_name) {
+            if let Err(e) = history.save(&*self.fs, &self.config_dir) {
+                warn!("Could not update the project history: {e}");
+            }
+        }
+        let event = TimeEvent::Rename {
+            from: prj.name.clone(),
+            to: new_name.to_string(),
+        };
+        if let Err(e) = append_time_event(&*self.fs, event, now()) {
+            warn!("Could not write the time log: {e}");
+        }
+
+        let renamed = HookProject {
+            name: new_name.to_string(),
+            path: new_path.clone(),
+        };
+        // The project is renamed already, a failing hook is reported like the ones of a change
+        let hook_result = self.dispatch(
+            LifecycleEvent::Rename,
+            &renamed,
+            Some(&HookProject::from(&prj)),
+            None,
+        );
+
+        hook_result.map(|_| RenameResult {
+            project: new_name.to_string(),
+            path: new_path,
+            old_project: prj_name.to_string(),
+        })
+    }
+
+    /// Runs the scripts of a lifecycle event after it happened to `project`, with the same runner as on-prj-change.
+    /// They get `PRJ` and `PRJ_PATH`, `OLD_PRJ` and `OLD_PRJ_PATH` for what the project was before a rename or move,
+    /// and `FOLDER` and `FOLDER_PATH` for the wechsel folder of the folder events.
+    /// They run in the project folder if it still exists.
+    pub fn dispatch(
+        &self,
+        event: LifecycleEvent,
+        project: &HookProject,
+        old_project: Option<&HookProject>,
+        folder: Option<&str>,
+    ) -> Result<(), WechselError> {
+        let config_dir = &self.config_dir;
+        let hooks = self.config()?.hooks;
+        if !hooks.enabled {
+            return Ok(());
+        }
+        let path = |path: &Path| path.to_str().unwrap_or_default().to_owned();
+        let mut env_vars: HashMap<String, String> = HashMap::from_iter(vec![
+            ("PRJ".to_owned(), project.name.clone()),
+            ("PRJ_PATH".to_owned(), path(&project.path)),
+        ]);
+        if let Some(old_project) = old_project {
+            env_vars.insert("OLD_PRJ".to_owned(), old_project.name.clone());
+            env_vars.insert("OLD_PRJ_PATH".to_owned(), path(&old_project.path));
+        }
+        if let Some(folder) = folder {
+            let folder_path = path_from_iter([&project.path, &PathBuf::from(folder)])
+                .with_extension(WECHSEL_FOLDER_EXTENSION);
+            env_vars.insert("FOLDER".to_owned(), folder.to_owned());
+            env_vars.insert("FOLDER_PATH".to_owned(), path(&folder_path));
+        }
+
+        let found = self.find_by_path(&project.path)?;
+        let event_doc = HookEvent {
+            project: Some(project.clone()),
+            old_project: old_project.cloned(),
+            new_chain: found
+                .as_ref()
+                .map(|found| project_chain(found).iter().map(HookProject::from).collect())
+                .unwrap_or_default(),
+            folder: folder.map(str::to_owned),
+            ..HookEvent::new(event.name())
+        };
+        let current_dir = Some(project.path.as_path()).filter(|path| self.fs.is_dir(path));
+        let script = lifecycle_script(event, config_dir);
+        debug!("Dispatching {} for {}", event.name(), project.name);
+        HookRunner::new(&hooks, config_dir, self.fs.clone())
+            .with_output(self.hook_output.clone())
+            .run_hooks(&script, &event_doc, &env_vars, current_dir)
+    }
+
+    /// Allows the scripts in the folder of a project to run as they are now
+    pub fn allow(&self, prj_name: &str) -> Result<Vec<AllowedScript>, WechselError> {
+        self.allow_at(&self.find(prj_name)?.path)
+    }
+
+    /// Like [`allow`](Workspace::allow) for the project with the folder `path`
+    pub fn allow_at(&self, path: &Path) -> Result<Vec<AllowedScript>, WechselError> {
+        let prj = self.project_at(path)?;
+        let fs = &*self.fs;
+        let mut trust = TrustStore::load(fs, &self.config_dir);
+        let mut allowed = vec![];
+        for script in project_scripts(fs, &prj.path) {
+            let hash = trust.allow(fs, &script)?;
+            info!("Allowed {script:?} with hash {hash}");
+            allowed.push(AllowedScript { script, hash });
+        }
+        trust.save(fs, &self.config_dir)?;
+        Ok(allowed)
+    }
+
+    /// Stops the scripts in the folder of a project from running, returns the ones that were allowed
+    pub fn deny(&self, prj_name: &str) -> Result<Vec<PathBuf>, WechselError> {
+        self.deny_at(&self.find(prj_name)?.path)
+    }
+
+    /// Like [`deny`](Workspace::deny) for the project with the folder `path`
+    pub fn deny_at(&self, path: &Path) -> Result<Vec<PathBuf>, WechselError> {
+        let prj = self.project_at(path)?;
+        let mut trust = TrustStore::load(&*self.fs, &self.config_dir);
+        let denied = trust.deny(&prj.path);
+        trust.save(&*self.fs, &self.config_dir)?;
+        Ok(denied)
+    }
+}
+
+/// The environment files for fish and bash with their content for a project,
+/// the variables hooks exported in `env` come after the ones of the project
+pub fn env_files(
+    fs: &dyn FileSystem,
+    prj_name: &str,
+    prj_path: &str,
+    env: &BTreeMap<String, String>,
+    config_dir: &PathBuf,
+) -> [(PathBuf, String); 2] {
+    let mut fish = format!("set -x PRJ {prj_name}\nset -x PRJ_PATH {prj_path}");
+    let mut bash = format!("export PRJ={prj_name}\nexport PRJ_PATH={prj_path}");
+    // The snippets only source the init scripts that were allowed,
+    // and check the hash before, as the scripts can change until the next change
+    let trust = TrustStore::load(fs, config_dir);
+    let fish_init = path_from_iter([Path::new(prj_path), Path::new(FISH_INIT_SCRIPT)]);
+    match trust.allowed_hash(fs, &fish_init) {
+        Some(hash) => fish.push_str(&format!(
+            "\nset -x PRJ_INIT_FISH {}\nset -x PRJ_INIT_FISH_SHA256 {hash}",
+            fish_quote(&fish_init)
+        )),
+        None => fish.push_str("\nset -e PRJ_INIT_FISH\nset -e PRJ_INIT_FISH_SHA256"),
+    }
+    let init = path_from_iter([Path::new(prj_path), Path::new(INIT_SCRIPT)]);
+    match trust.allowed_hash(fs, &init) {
+        Some(hash) => bash.push_str(&format!(
+            "\nexport PRJ_INIT={}\nexport PRJ_INIT_SHA256={hash}",
+            bash_quote(&init)
+        )),
+        None => bash.push_str("\nunset PRJ_INIT PRJ_INIT_SHA256"),
+    }
+    for (name, value) in env {
+        fish.push_str(&format!("\nset -x {name} {}", fish_quote(value)));
+        bash.push_str(&format!("\nexport {name}={}", bash_quote(value)));
+    }
+    [
+        (get_environment_vars_fish_path(config_dir), fish),
+        (get_environment_vars_path(config_dir), bash),
+    ]
+}
+
+fn fish_quote(value: impl AsRef<OsStr>) -> String {
+    let value = value.as_ref().to_string_lossy();
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn bash_quote(value: impl AsRef<OsStr>) -> String {
+    let value = value.as_ref().to_string_lossy();
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+pub fn write_env_files(
+    fs: &dyn FileSystem,
+    prj_name: &str,
+    prj_path: &str,
+    env: &BTreeMap<String, String>,
+    config_dir: &PathBuf,
+) -> io::Result<()> {
+    for (path, content) in env_files(fs, prj_name, prj_path, env, config_dir) {
+        fs.write(&path, content.as_bytes())?;
+        debug!("Wrote {path:?}");
+    }
+    fs.write(&hook_env_path(config_dir), &serde_json::to_vec(env)?)?;
+    Ok(())
+}
+
+/// The variables the hooks exported for the active project
+pub fn read_hook_env(fs: &dyn FileSystem, config_dir: &PathBuf) -> BTreeMap<String, String> {
+    fs.read(&hook_env_path(config_dir))
+        .ok()
+        .and_then(|content| serde_json::from_slice(&content).ok())
+        .unwrap_or_default()
+}
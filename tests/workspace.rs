@@ -0,0 +1,281 @@
+use std::{env, fs, path::PathBuf, rc::Rc};
+
+use wechsel::{
+    CURRENT_PROJECT_FOLDER, ChangeOptions, ConflictStrategy, FileSystem, History, MemoryFileSystem,
+    Navigation, Operation, WechselError, Workspace, get_environment_vars_path, get_state_dir,
+    record_switch, time_log_path,
+};
+
+// A home folder that only exists in memory, the config dir is on disk for the config file and the lock
+// and in memory for the files wechsel writes there
+struct MemoryHome {
+    home: PathBuf,
+    config_dir: PathBuf,
+    memory: Rc<MemoryFileSystem>,
+    workspace: Workspace,
+}
+
+impl MemoryHome {
+    fn new(test: &str) -> MemoryHome {
+        let config_dir =
+            env::temp_dir().join(format!("wechsel_memory_{test}_{}", std::process::id()));
+        fs::create_dir_all(&config_dir).unwrap();
+        let home = PathBuf::from("/home/user");
+        let memory = Rc::new(MemoryFileSystem::new());
+        memory.create_dir_all(&config_dir).unwrap();
+        for folder in [
+            "home.p/Desktop.w",
+            "home.p/Music.w",
+            "home.p/lab.p/Desktop.w",
+            "home.p/lab.p/deep.p",
+            "home.p/notes.txt.p",
+        ] {
+            memory.create_dir_all(&home.join(folder)).unwrap();
+        }
+        memory.add_file(&home.join("home.p/readme.p")).unwrap();
+        let workspace = Workspace::new(&home, &config_dir).with_file_system(memory.clone());
+        MemoryHome {
+            home,
+            config_dir,
+            memory,
+            workspace,
+        }
+    }
+
+    fn link(&self, name: &str) -> PathBuf {
+        self.memory.read_link(&self.home.join(name)).unwrap()
+    }
+
+    // A real Desktop folder in the home folder, where the link of the project should go
+    fn add_desktop(&self) {
+        for file in ["Desktop/notes.txt", "Desktop/todo.txt", "Desktop/sub/a.txt"] {
+            self.memory.add_file(&self.home.join(file)).unwrap();
+        }
+        self.memory
+            .add_file(&self.home.join("home.p/Desktop.w/todo.txt"))
+            .unwrap();
+    }
+
+    fn change(&self, prj_name: &str, conflict: ConflictStrategy) {
+        let options = ChangeOptions {
+            conflict: Some(conflict),
+            ..ChangeOptions::default()
+        };
+        self.workspace.change(prj_name, options).unwrap();
+    }
+
+    fn assert_merged(&self) {
+        assert_eq!(self.link("Desktop"), self.home.join("home.p/Desktop.w"));
+        for file in ["notes.txt", "todo.txt", "todo (1).txt", "sub/a.txt"] {
+            assert!(
+                self.memory
+                    .exists(&self.home.join("home.p/Desktop.w").join(file)),
+                "{file} was not merged"
+            );
+        }
+    }
+}
+
+impl Drop for MemoryHome {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.config_dir);
+    }
+}
+
+#[test]
+fn tree_discovery() {
+    let test = MemoryHome::new("tree");
+    let tree = test.workspace.tree(true).unwrap();
+    assert_eq!(tree.prj_name, "home");
+    let mut children: Vec<_> = tree.children.iter().map(|c| c.prj_name.clone()).collect();
+    children.sort();
+    assert_eq!(children, ["lab", "notes.txt"], "Files are not projects");
+    assert_eq!(
+        tree.folders.as_deref(),
+        Some(&["Desktop".to_string(), "Music".to_string()][..])
+    );
+    assert!(test.workspace.find("deep").is_ok());
+
+    test.memory.fail(Operation::ReadDir, test.home.clone());
+    assert!(matches!(
+        test.workspace.tree(false),
+        Err(WechselError::Io(_))
+    ));
+}
+
+#[test]
+fn inherited_folders() {
+    let test = MemoryHome::new("inherit");
+    // lab has no Music folder of its own, the one of home is linked
+    test.workspace
+        .change("deep", ChangeOptions::default())
+        .unwrap();
+    assert_eq!(
+        test.link(CURRENT_PROJECT_FOLDER),
+        test.home.join("home.p/lab.p/deep.p")
+    );
+    assert_eq!(
+        test.link("Desktop"),
+        test.home.join("home.p/lab.p/Desktop.w")
+    );
+    assert_eq!(test.link("Music"), test.home.join("home.p/Music.w"));
+    assert_eq!(test.workspace.active().as_deref(), Some("deep"));
+}
+
+#[test]
+fn failing_symlink_is_skipped() {
+    let test = MemoryHome::new("symlink");
+    test.workspace
+        .change("deep", ChangeOptions::default())
+        .unwrap();
+    // A link that can't be created is skipped, the others are still changed
+    test.memory
+        .fail(Operation::Symlink, test.home.join("Desktop"));
+    test.workspace
+        .change("home", ChangeOptions::default())
+        .unwrap();
+    assert!(!test.memory.is_symlink(&test.home.join("Desktop")));
+    assert_eq!(test.link(CURRENT_PROJECT_FOLDER), test.home.join("home.p"));
+}
+
+#[test]
+fn failing_remove_stops_the_change() {
+    let test = MemoryHome::new("remove");
+    test.workspace
+        .change("lab", ChangeOptions::default())
+        .unwrap();
+    test.memory
+        .fail(Operation::RemoveFile, test.home.join("Music"));
+    assert!(matches!(
+        test.workspace.change("home", ChangeOptions::default()),
+        Err(WechselError::Io(_))
+    ));
+    // The links before the failing one are already changed
+    assert_eq!(test.link(CURRENT_PROJECT_FOLDER), test.home.join("home.p"));
+    assert_eq!(test.link("Desktop"), test.home.join("home.p/Desktop.w"));
+}
+
+#[test]
+fn links_follow_a_rename() {
+    let test = MemoryHome::new("rename");
+    test.workspace
+        .change("lab", ChangeOptions::default())
+        .unwrap();
+    record_switch(
+        &*test.memory,
+        "home",
+        "lab",
+        Navigation::Switch,
+        &test.config_dir,
+    )
+    .unwrap();
+    let renamed = test.workspace.rename("lab", "bench").unwrap();
+    assert_eq!(test.link("Desktop"), renamed.path.join("Desktop.w"));
+    assert!(matches!(
+        test.workspace.find("lab"),
+        Err(WechselError::NotFound(_))
+    ));
+    let history = History::load(&*test.memory, &test.config_dir);
+    assert_eq!(history.switches[0].to, "bench");
+    // The time log is only appended to
+    let log = time_log_path(&get_state_dir().unwrap());
+    let log = String::from_utf8(test.memory.read(&log).unwrap()).unwrap();
+    assert!(log.ends_with("\"event\":\"rename\",\"from\":\"lab\",\"to\":\"bench\"}\n"));
+}
+
+#[test]
+fn change_by_path_with_duplicate_names() {
+    let test = MemoryHome::new("duplicates");
+    let deep = test.home.join("home.p/deep.p");
+    test.memory.create_dir_all(&deep).unwrap();
+    assert!(matches!(
+        test.workspace.change("deep", ChangeOptions::default()),
+        Err(WechselError::Ambiguous(..))
+    ));
+    let changed = test
+        .workspace
+        .change_at(&deep, ChangeOptions::default())
+        .unwrap();
+    assert_eq!(changed.project, "deep");
+    assert_eq!(test.link(CURRENT_PROJECT_FOLDER), deep);
+}
+
+#[test]
+fn env_files_in_memory() {
+    let test = MemoryHome::new("env");
+    test.workspace
+        .change("lab", ChangeOptions::default())
+        .unwrap();
+    let env_file = get_environment_vars_path(&test.config_dir);
+    assert!(!env_file.exists());
+    let env = String::from_utf8(test.memory.read(&env_file).unwrap()).unwrap();
+    assert!(env.contains("export PRJ=lab\n"));
+    // The variables follow the renamed folder of the active project
+    let renamed = test.workspace.rename("lab", "bench").unwrap();
+    let env = String::from_utf8(test.memory.read(&env_file).unwrap()).unwrap();
+    assert!(env.contains(&format!(
+        "export PRJ=bench\nexport PRJ_PATH={}\n",
+        renamed.path.display()
+    )));
+
+    test.memory.fail(Operation::Write, &env_file);
+    assert!(matches!(
+        test.workspace.change("home", ChangeOptions::default()),
+        Err(WechselError::Io(_))
+    ));
+}
+
+#[test]
+fn merge_in_memory() {
+    let test = MemoryHome::new("merge");
+    test.add_desktop();
+    test.change("home", ConflictStrategy::Merge);
+    test.assert_merged();
+}
+
+#[test]
+fn merge_across_filesystems() {
+    let test = MemoryHome::new("exdev");
+    test.add_desktop();
+    // Renames to another filesystem fail, the entries are copied instead
+    for entry in ["Desktop/notes.txt", "Desktop/sub"] {
+        test.memory
+            .fail_with_os_error(Operation::Rename, test.home.join(entry), libc::EXDEV);
+    }
+    test.change("home", ConflictStrategy::Merge);
+    test.assert_merged();
+}
+
+#[test]
+fn stash_in_memory() {
+    let test = MemoryHome::new("stash");
+    test.add_desktop();
+    test.change("home", ConflictStrategy::Stash);
+    assert_eq!(test.link("Desktop"), test.home.join("home.p/Desktop.w"));
+    let backups: Vec<_> = test
+        .memory
+        .read_dir(&test.home)
+        .unwrap()
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("Desktop.backup-"))
+        })
+        .collect();
+    assert_eq!(backups.len(), 1, "Desktop was not stashed");
+    assert!(test.memory.exists(&backups[0].join("sub/a.txt")));
+    assert!(
+        !test
+            .memory
+            .exists(&test.home.join("home.p/Desktop.w/notes.txt"))
+    );
+}
+
+#[test]
+fn skip_keeps_the_folder() {
+    let test = MemoryHome::new("skip");
+    test.add_desktop();
+    test.change("home", ConflictStrategy::Skip);
+    assert!(!test.memory.is_symlink(&test.home.join("Desktop")));
+    assert!(test.memory.exists(&test.home.join("Desktop/notes.txt")));
+}
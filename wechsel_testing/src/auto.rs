@@ -0,0 +1,161 @@
+use std::{
+    fs,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use wechsel::{config_file_path, get_config_dir, home_dir};
+
+use crate::{get_current_tree, utils::*};
+
+const EVERY_DAY: &str = r#"days = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"]"#;
+
+pub(crate) fn auto_test() {
+    let home_dir = home_dir().expect("could not find home dir");
+    let config = config_file_path(&get_config_dir().expect("could not find config dir"));
+    let previous_config = fs::read_to_string(&config).ok();
+
+    let wechsel = |args: &[&str]| {
+        let output = call_as_user(&[&[PATH_TO_WECHSEL_BINARY], args].concat(), &home_dir);
+        print_command_output(output.clone());
+        output
+    };
+    let write_config = |grace_period: u64, rules: &[&str]| {
+        let rules: String = rules
+            .iter()
+            .map(|rule| format!("\n[[auto.rules]]\n{rule}\n"))
+            .collect();
+        fs::write(
+            &config,
+            format!("[auto]\ngrace_period = {grace_period}\ninterval = 1\n{rules}"),
+        )
+        .unwrap();
+    };
+    let active = || get_current_tree(false).unwrap().active;
+
+    // The root project, a child and a grandchild, like the report test left them
+    let root = get_current_tree(true).unwrap().tree.prj_name;
+    let [a, b, c] = [root.as_str(), "report-b", "report-c"];
+    let never = format!("project = {c:?}\nprobe = \"false\"");
+    let always = format!("project = {b:?}\n{EVERY_DAY}\nfrom = \"00:00\"");
+    let invalid = format!("project = {b:?}\nfrom = \"25:00\"");
+
+    println!("-- auto: invalid rules --");
+    for rule in [
+        format!("project = {b:?}\ndays = [\"someday\"]"),
+        format!("project = {b:?}\nfrom = \"25:00\""),
+        format!("project = {b:?}\nto = \"noon\""),
+    ] {
+        write_config(0, &[&rule]);
+        let output = wechsel(&["auto"]);
+        assert_eq!(
+            output.status.code(),
+            Some(1),
+            "An invalid rule was accepted"
+        );
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("auto.rules"),
+            "The error doesn't name the invalid rule"
+        );
+        let output = wechsel(&["config", "set", "auto.interval", "5"]);
+        assert_eq!(
+            output.status.code(),
+            Some(1),
+            "config set accepted a config with an invalid rule"
+        );
+    }
+
+    println!("-- auto: rules --");
+    write_config(3600, &[never.as_str(), always.as_str()]);
+    assert!(wechsel(&["change", a]).status.success());
+    assert!(wechsel(&["auto"]).status.success());
+    assert_eq!(
+        active(),
+        a,
+        "auto changed the project during the grace period"
+    );
+
+    write_config(0, &[never.as_str(), always.as_str()]);
+    let output = wechsel(&["--json", "auto"]);
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(!output["result"].is_null(), "auto didn't report the change");
+    assert_eq!(active(), b, "auto didn't change to the project of the rule");
+    let output = wechsel(&["--json", "auto"]);
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(
+        output["result"].is_null(),
+        "auto changed to the active project"
+    );
+
+    write_config(0, &[never.as_str()]);
+    let output = wechsel(&["--json", "auto"]);
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(
+        output["result"].is_null(),
+        "auto changed without a matching rule"
+    );
+    assert_eq!(active(), b);
+
+    // A probe that hangs is killed and doesn't match
+    write_config(0, &[&format!("project = {c:?}\nprobe = \"sleep 60\"")]);
+    let start = Instant::now();
+    assert!(wechsel(&["auto"]).status.success());
+    assert!(
+        start.elapsed() < Duration::from_secs(30),
+        "auto waited for a hanging probe"
+    );
+    assert_eq!(active(), b, "The rule of a hanging probe was applied");
+
+    write_config(0, &["project = \"auto-missing\""]);
+    assert_eq!(
+        wechsel(&["auto"]).status.code(),
+        Some(3),
+        "A rule for a missing project was applied"
+    );
+
+    println!("-- auto: daemon --");
+    write_config(0, &[never.as_str()]);
+    assert!(wechsel(&["change", a]).status.success());
+    let daemon = || {
+        Command::new(PATH_TO_WECHSEL_BINARY)
+            .arg("daemon")
+            .current_dir(&home_dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap()
+    };
+
+    // A broken config is still an error on start
+    write_config(0, &[&invalid]);
+    let status = daemon().wait().unwrap();
+    assert_eq!(
+        status.code(),
+        Some(1),
+        "The daemon started with an invalid rule"
+    );
+
+    write_config(0, &[never.as_str()]);
+    let mut child = KillOnDrop(daemon());
+    thread::sleep(Duration::from_millis(1500));
+    write_config(0, &[&invalid]);
+    thread::sleep(Duration::from_millis(1500));
+    assert!(
+        child.0.try_wait().unwrap().is_none(),
+        "The daemon stopped on an invalid rule"
+    );
+
+    write_config(0, &[always.as_str()]);
+    let start = Instant::now();
+    while active() != b && start.elapsed() < Duration::from_secs(5) {
+        thread::sleep(Duration::from_millis(100));
+    }
+    drop(child);
+    assert_eq!(active(), b, "The daemon didn't pick up the fixed config");
+
+    match previous_config {
+        Some(previous_config) => fs::write(&config, previous_config).unwrap(),
+        None => fs::remove_file(&config).unwrap(),
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+use wechsel::home_dir;
+
+use crate::{Project, utils::*};
+
+const DBUS_CONFIG: &str = r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:tmpdir=/tmp</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>"#;
+
+// The service is an optional feature, the test is skipped if the binary was built without it
+pub(crate) fn dbus_available() -> bool {
+    Command::new(PATH_TO_WECHSEL_BINARY)
+        .args(["help", "service"])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or_default()
+        && Command::new("dbus-daemon")
+            .arg("--version")
+            .output()
+            .is_ok()
+}
+
+pub(crate) fn dbus_test(prj: &Project, other: &Project) {
+    println!("-- dbus --");
+    let home_dir = home_dir().expect("could not find home dir");
+
+    let config = PathBuf::from("/tmp/wechsel-test-dbus.conf");
+    fs::write(&config, DBUS_CONFIG).unwrap();
+    let mut daemon = Command::new("dbus-daemon")
+        .arg(format!("--config-file={}", config.to_str().unwrap()))
+        .arg("--print-address")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Could not start dbus-daemon");
+
+    let mut address = String::new();
+    BufReader::new(daemon.stdout.take().unwrap())
+        .read_line(&mut address)
+        .unwrap();
+    let address = address.trim().to_string();
+    let _daemon = KillOnDrop(daemon);
+
+    let _service = KillOnDrop(
+        Command::new(PATH_TO_WECHSEL_BINARY)
+            .arg("service")
+            .env("DBUS_SESSION_BUS_ADDRESS", &address)
+            .current_dir(&home_dir)
+            .spawn()
+            .expect("Could not start the wechsel service"),
+    );
+    thread::sleep(Duration::from_secs(1));
+
+    let call = |method: &str, args: &[&str]| {
+        Command::new("dbus-send")
+            .env("DBUS_SESSION_BUS_ADDRESS", &address)
+            .args([
+                "--session",
+                "--print-reply",
+                "--dest=org.wechsel.Manager",
+                "/org/wechsel/Manager",
+                &format!("org.wechsel.Manager.{method}"),
+            ])
+            .args(args)
+            .output()
+            .expect("Could not call dbus-send")
+    };
+
+    let output = call("Change", &[&format!("string:{}", prj.name)]);
+    assert!(output.status.success(), "Change over D-Bus failed");
+
+    let output = call("GetActive", &[]);
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .contains(&format!("\"{}\"", prj.name)),
+        "GetActive over D-Bus did not return the new project"
+    );
+
+    let output = call("Change", &["string:does-not-exist"]);
+    assert!(
+        !output.status.success(),
+        "Changing to a missing project over D-Bus should fail"
+    );
+    // Names are never taken for options of the command line
+    for name in ["--help", "-q"] {
+        let output = call("Change", &[&format!("string:{name}")]);
+        assert!(
+            !output.status.success(),
+            "Changing to {name} over D-Bus should fail"
+        );
+    }
+
+    let output = call("ListProjects", &[]);
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .contains(&format!("\"name\":\"{}\"", other.name)),
+        "ListProjects over D-Bus did not return the tree"
+    );
+
+    let mut monitor = Command::new("dbus-monitor")
+        .arg("--address")
+        .arg(&address)
+        .arg("type='signal',interface='org.wechsel.Manager'")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Could not start dbus-monitor");
+    let (sender, signals) = mpsc::channel();
+    let stdout = monitor.stdout.take().unwrap();
+    let _monitor = KillOnDrop(monitor);
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(member) = line.split("member=").nth(1) {
+                if sender.send(member.to_string()).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    thread::sleep(Duration::from_millis(500));
+
+    let output = call("Change", &[&format!("string:{}", other.name)]);
+    assert!(output.status.success(), "Change over D-Bus failed");
+    wait_for_signal(&signals, "ProjectChanged");
+
+    println!("-- dbus: create --");
+    let output = call(
+        "CreateProject",
+        &[
+            "string:dbus-new",
+            &format!("string:{}", prj.name),
+            "array:string:Desktop,My Music",
+        ],
+    );
+    assert!(output.status.success(), "CreateProject over D-Bus failed");
+    let new_path = prj.path.join("dbus-new.p");
+    for folder in ["Desktop.w", "My Music.w"] {
+        assert!(
+            new_path.join(folder).is_dir(),
+            "CreateProject didn't create the project with {folder}"
+        );
+    }
+    wait_for_signal(&signals, "TreeChanged");
+
+    // An invalid folder name is refused before anything is created
+    let output = call(
+        "CreateProject",
+        &[
+            "string:dbus-empty",
+            &format!("string:{}", prj.name),
+            "array:string:Desktop,..",
+        ],
+    );
+    assert!(
+        !output.status.success(),
+        "An invalid folder name was accepted"
+    );
+    assert!(!prj.path.join("dbus-empty.p").exists());
+
+    // Changes made outside of the service are noticed as well
+    thread::sleep(Duration::from_millis(500));
+    while signals.try_recv().is_ok() {}
+    fs::create_dir(prj.path.join("dbus-manual.p")).unwrap();
+    wait_for_signal(&signals, "TreeChanged");
+    fs::remove_dir(prj.path.join("dbus-manual.p")).unwrap();
+    wait_for_signal(&signals, "TreeChanged");
+}
+
+// Well below the interval the service used to re-scan the tree in
+fn wait_for_signal(signals: &Receiver<String>, member: &str) {
+    let start = Instant::now();
+    while let Some(timeout) = Duration::from_secs(2).checked_sub(start.elapsed()) {
+        match signals.recv_timeout(timeout) {
+            Ok(signal) if signal == member => return,
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    panic!("The service did not emit {member}");
+}
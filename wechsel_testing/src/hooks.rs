@@ -0,0 +1,666 @@
+use std::{
+    fs,
+    os::unix::{fs::PermissionsExt, process::ExitStatusExt},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use wechsel::{
+    CURRENT_PROJECT_FOLDER, INIT_SCRIPT, ON_ENTER_HOOK, ON_LEAVE_HOOK, PRE_CHANGE_HOOK,
+    PROJECT_EXTENSION, get_config_dir, get_environment_vars_path, get_state_dir, home_dir,
+    log_file_path, path_from_iter, time_log_path,
+};
+
+use crate::{init_test, utils::*};
+
+pub(crate) fn hooks_test() {
+    security_check();
+    let home_dir = home_dir().expect("could not find home dir");
+    setup_home(&home_dir, true);
+    let home_prj = init_test();
+
+    // home/uni/{ml,os}
+    let uni = new_nested("uni", &home_prj.name, &home_prj.path, &home_dir);
+    let ml = new_nested("ml", "uni", &uni, &home_dir);
+    let os = new_nested("os", "uni", &uni, &home_dir);
+    // `new` changes to the new project
+    let output = call_as_user(
+        &[PATH_TO_WECHSEL_BINARY, "change", &home_prj.name],
+        &home_dir,
+    );
+    assert!(output.status.success());
+    let log = path_from_iter([&home_dir, &PathBuf::from("hooks.log")]);
+    for prj in [&home_prj.path, &uni, &ml, &os] {
+        write_hook(prj, ON_ENTER_HOOK, "enter", &log);
+        write_hook(prj, ON_LEAVE_HOOK, "leave", &log);
+    }
+    // Nothing runs before the hooks are allowed
+    enter_leave_test("ml", &log, &[]);
+    enter_leave_test(&home_prj.name, &log, &[]);
+    for prj in [&home_prj.name, "uni", "ml", "os"] {
+        allow_test(prj);
+    }
+
+    enter_leave_test(
+        "ml",
+        &log,
+        &["enter uni home home/uni/ml", "enter ml home home/uni/ml"],
+    );
+    enter_leave_test(
+        "os",
+        &log,
+        &[
+            "leave ml home/uni/ml home/uni/os",
+            "enter os home/uni/ml home/uni/os",
+        ],
+    );
+    enter_leave_test("os", &log, &[]);
+    enter_leave_test(
+        &home_prj.name,
+        &log,
+        &["leave os home/uni/os home", "leave uni home/uni/os home"],
+    );
+
+    veto_test(&home_dir, &uni, &log);
+    hook_dir_test(&home_dir);
+    runner_test(&home_dir);
+    json_test(&home_dir, &home_prj.name, &uni);
+    trust_test(&home_dir, &home_prj.name, &ml, &log);
+    rename_test(&home_dir, &home_prj.name, &uni, &log);
+}
+
+// A renamed project keeps its history, its tracked time, its allowed scripts and the exported variables
+fn rename_test(home_dir: &PathBuf, home_name: &str, uni: &Path, log: &Path) {
+    println!("-- rename --");
+    let config_dir = get_config_dir().expect("Could not find config dir");
+    let wechsel = |args: &[&str]| {
+        let output = call_as_user(&[&[PATH_TO_WECHSEL_BINARY], args].concat(), home_dir);
+        print_command_output(output.clone());
+        assert!(output.status.success(), "wechsel {args:?} failed");
+        output
+    };
+    let exports = |line: &str| {
+        fs::read_to_string(get_environment_vars_path(&config_dir))
+            .unwrap()
+            .lines()
+            .any(|l| l == line)
+    };
+
+    let dir = path_from_iter([&config_dir, &PathBuf::from("on-prj-change.d")]);
+    fs::create_dir_all(&dir).unwrap();
+    write_script(
+        &path_from_iter([&dir, &PathBuf::from("10-json")]),
+        "#!/bin/sh\n# wechsel: json\necho '{\"env\": {\"VPN\": \"on\"}}'\n",
+    );
+    wechsel(&["change", home_name]);
+    wechsel(&["change", "ml"]);
+    fs::remove_dir_all(dir).unwrap();
+    assert!(exports("export VPN='on'"));
+
+    wechsel(&["rename", "ml", "dl"]);
+    let dl = path_from_iter([uni, Path::new("dl")]).with_extension(PROJECT_EXTENSION);
+    assert!(exports("export PRJ=dl"));
+    assert!(exports(&format!("export PRJ_PATH={}", dl.display())));
+    assert!(
+        exports("export VPN='on'"),
+        "The variables of the hooks got lost in the rename"
+    );
+
+    enter_leave_test(
+        home_name,
+        log,
+        &["leave dl home/uni/dl home", "leave uni home/uni/dl home"],
+    );
+    let output = wechsel(&["--json", "history", "-n", "1"]);
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        output["result"][0]["from"], "dl",
+        "The history has the old name"
+    );
+    fs::remove_file(log).unwrap();
+    wechsel(&["back"]);
+    assert_eq!(
+        fs::read_to_string(log)
+            .unwrap_or_default()
+            .lines()
+            .collect::<Vec<_>>(),
+        ["enter uni home home/uni/dl", "enter dl home home/uni/dl"],
+        "The allowed scripts are not allowed anymore after the rename"
+    );
+
+    let output = wechsel(&["--json", "report"]);
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let projects: Vec<&str> = output["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|row| row["project"].as_str())
+        .collect();
+    assert!(projects.contains(&"dl"), "The time of the project was lost");
+    assert!(!projects.contains(&"ml"), "The report has the old name");
+    // The time log is only appended to, the report maps the old name when it reads it
+    let time_log = fs::read_to_string(time_log_path(&get_state_dir().unwrap())).unwrap();
+    assert!(time_log.contains(r#""event":"switch","project":"ml""#));
+    assert!(time_log.contains(r#""event":"rename","from":"ml","to":"dl""#));
+
+    // A failing on-prj-rename hook doesn't undo the rename
+    let hook = path_from_iter([&config_dir, &PathBuf::from("on-prj-rename")]);
+    write_script(&hook, "#!/bin/sh\nexit 1\n");
+    let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "rename", "dl", "ml"], home_dir);
+    fs::remove_file(hook).unwrap();
+    assert_eq!(
+        output.status.code(),
+        Some(8),
+        "The failing hook was not reported"
+    );
+    let output = wechsel(&["--json", "history", "-n", "1"]);
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        output["result"][0]["to"], "ml",
+        "The history wasn't renamed after the hook failed"
+    );
+}
+
+// A project script that changed since it was allowed is skipped until it is allowed again
+fn trust_test(home_dir: &PathBuf, home_name: &str, ml: &Path, log: &Path) {
+    println!("-- allow/deny --");
+    let change = |prj: &str| {
+        let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "change", prj], home_dir);
+        assert!(output.status.success());
+    };
+    let exports = |line: &str| {
+        fs::read_to_string(get_environment_vars_path(
+            &get_config_dir().expect("Could not find config dir"),
+        ))
+        .unwrap()
+        .lines()
+        .any(|l| l == line)
+    };
+    change(home_name);
+    let hook = path_from_iter([ml, Path::new(ON_ENTER_HOOK)]);
+    let content = fs::read_to_string(&hook).unwrap();
+    write_script(&hook, &format!("{content}# changed\n"));
+    enter_leave_test("ml", log, &["enter uni home home/uni/ml"]);
+    change(home_name);
+    let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "change", "ml"], home_dir);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(
+        stderr.matches(&format!("{hook:?}")).count(),
+        1,
+        "The changed hook wasn't warned about once:\n{stderr}"
+    );
+    change(home_name);
+    allow_test("ml");
+    enter_leave_test(
+        "ml",
+        log,
+        &["enter uni home home/uni/ml", "enter ml home home/uni/ml"],
+    );
+
+    // The init script is only handed to the shell once it is allowed
+    let init = path_from_iter([ml, Path::new(INIT_SCRIPT)]);
+    write_script(&init, "echo init\n");
+    change(home_name);
+    change("ml");
+    assert!(exports("unset PRJ_INIT PRJ_INIT_SHA256"));
+    allow_test("ml");
+    change(home_name);
+    change("ml");
+    assert!(
+        exports(&format!("export PRJ_INIT='{}'", init.display())),
+        "The allowed init script is not exported"
+    );
+    // The snippet checks the hash, a script that changed after the change isn't sourced
+    let sourced = || {
+        let env = get_environment_vars_path(&get_config_dir().expect("Could not find config dir"));
+        let snippet = format!(
+            ". {env:?}\n{}",
+            include_str!("../../config_files/default_bash_config")
+        );
+        let output = call_as_user(&["bash", "-c", &snippet], home_dir);
+        String::from_utf8_lossy(&output.stdout).contains("init")
+    };
+    assert!(sourced(), "The allowed init script wasn't sourced");
+    write_script(&init, "echo init\necho changed\n");
+    assert!(!sourced(), "The init script was sourced after it changed");
+    write_script(&init, "echo init\n");
+
+    // Allowed hooks run the content that was hashed, even if the file changes while they run
+    let hook_dir = path_from_iter([ml, Path::new(ON_ENTER_HOOK)]).with_extension("d");
+    let hook = path_from_iter([&hook_dir, Path::new("grow")]);
+    let grow_log = path_from_iter([home_dir, &PathBuf::from("grow.log")]);
+    fs::create_dir_all(&hook_dir).unwrap();
+    write_script(
+        &hook,
+        &format!(
+            "#!/bin/bash\necho ran >> {grow_log:?}\necho 'echo appended >> {grow_log:?}' >> {hook:?}\n"
+        ),
+    );
+    allow_test("ml");
+    change(home_name);
+    change("ml");
+    assert_eq!(
+        fs::read_to_string(&grow_log).unwrap(),
+        "ran\n",
+        "The hook ran what was written to it after it was allowed"
+    );
+    fs::remove_dir_all(hook_dir).unwrap();
+    fs::remove_file(grow_log).unwrap();
+
+    let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "deny", "ml"], home_dir);
+    assert!(output.status.success());
+    enter_leave_test(home_name, log, &["leave uni home/uni/ml home"]);
+    change("ml");
+    assert!(exports("unset PRJ_INIT PRJ_INIT_SHA256"));
+    fs::remove_file(init).unwrap();
+    allow_test("ml");
+}
+
+// Hooks that opt into the JSON protocol get the change on stdin and can export variables
+fn json_test(home_dir: &PathBuf, home_name: &str, uni: &Path) {
+    println!("-- json protocol --");
+    let config_dir = get_config_dir().expect("Could not find config dir");
+    let dir = path_from_iter([&config_dir, &PathBuf::from("on-prj-change.d")]);
+    let os = path_from_iter([uni, Path::new("os")]).with_extension(PROJECT_EXTENSION);
+    let ml = path_from_iter([uni, Path::new("ml")]).with_extension(PROJECT_EXTENSION);
+    let enter_dir = path_from_iter([&os, &PathBuf::from(".on-enter.d")]);
+    fs::create_dir_all(&dir).unwrap();
+    fs::create_dir_all(&enter_dir).unwrap();
+    let doc = path_from_iter([home_dir, &PathBuf::from("change.json")]);
+    let enter_doc = path_from_iter([home_dir, &PathBuf::from("enter.json")]);
+    write_script(
+        &path_from_iter([&dir, &PathBuf::from("10-json")]),
+        &format!(
+            r#"#!/bin/sh
+# wechsel: json
+cat > {doc:?}
+echo '{{"env": {{"VPN": "it'\''s on", "1BAD": "x"}}, "messages": ["Connected"]}}'
+"#
+        ),
+    );
+    write_script(
+        &path_from_iter([&enter_dir, &PathBuf::from("10-json")]),
+        &format!("#!/bin/sh\n# wechsel: json\ncat > {enter_doc:?}\n"),
+    );
+    allow_test("os");
+    let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "change", "ml"], home_dir);
+    assert!(output.status.success());
+
+    let output = call_as_user(
+        &[PATH_TO_WECHSEL_BINARY, "change", "os", "--json"],
+        home_dir,
+    );
+    assert!(output.status.success());
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        result["result"]["env"],
+        serde_json::json!({ "VPN": "it's on" })
+    );
+    assert_eq!(
+        result["result"]["messages"],
+        serde_json::json!(["Connected"])
+    );
+
+    let read = |path: &PathBuf| -> serde_json::Value {
+        serde_json::from_str(&fs::read_to_string(path).expect("The hook got no input")).unwrap()
+    };
+    let doc = read(&doc);
+    assert_eq!(doc["version"], 1);
+    assert_eq!(doc["event"], "on-prj-change");
+    assert_eq!(doc["project"]["name"], "os");
+    assert_eq!(doc["old_project"]["name"], "ml");
+    let names = |chain: &serde_json::Value| -> Vec<String> {
+        chain
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|prj| prj["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(names(&doc["new_chain"]), [home_name, "uni", "os"]);
+    assert_eq!(names(&doc["old_chain"]), [home_name, "uni", "ml"]);
+    let has_link = |action: &str, source: &Path| {
+        doc["links"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|link| link["action"] == action && link["source"].as_str() == source.to_str())
+    };
+    assert!(has_link("removed", &ml), "The old project link is missing");
+    assert!(has_link("created", &os), "The new project link is missing");
+
+    let enter_doc = read(&enter_doc);
+    assert_eq!(enter_doc["event"], "on-enter");
+    assert_eq!(enter_doc["hook_project"]["name"], "os");
+
+    // The shells get the exported variable
+    let env_file = path_from_iter([&config_dir, &PathBuf::from("environment_variables.sh")]);
+    let output = call_as_user(
+        &[
+            "bash",
+            "-c",
+            &format!(". {env_file:?} && printf %s \"$VPN\""),
+        ],
+        home_dir,
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "it's on");
+    // The bash snippet finds the config dir of the temporary home as well
+    let snippet = include_str!("../../config_files/default_bash_config");
+    let output = call_as_user(
+        &["bash", "-c", &format!("{snippet}\nprintf %s \"$VPN\"")],
+        home_dir,
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "it's on",
+        "The bash snippet doesn't source the variables of the config dir"
+    );
+    let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "doctor"], home_dir);
+    assert!(
+        !String::from_utf8_lossy(&output.stdout).contains("doesn't match the active project"),
+        "The doctor doesn't accept exported variables"
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+    fs::remove_dir_all(enter_dir).unwrap();
+}
+
+// The timeout of the config, shebang lines and the output in the log file
+fn runner_test(home_dir: &PathBuf) {
+    println!("-- hook runner --");
+    let config_dir = get_config_dir().expect("Could not find config dir");
+    let dir = path_from_iter([&config_dir, &PathBuf::from("on-prj-change.d")]);
+    fs::create_dir_all(&dir).unwrap();
+    let log = path_from_iter([home_dir, &PathBuf::from("runner.log")]);
+    let pid_file = path_from_iter([home_dir, &PathBuf::from("runner.pid")]);
+    let change = |prj: &str| {
+        call_as_user(&[PATH_TO_WECHSEL_BINARY, "change", prj], home_dir)
+            .status
+            .code()
+    };
+
+    // Runs with awk, which sh couldn't
+    write_script(
+        &path_from_iter([&dir, &PathBuf::from("10-awk")]),
+        &format!(
+            "#!/usr/bin/awk -f\nBEGIN {{ print ENVIRON[\"PRJ\"] > {log:?}; print \"awk says hi\" }}\n"
+        ),
+    );
+    assert_eq!(change("os"), Some(0));
+    assert_eq!(
+        fs::read_to_string(&log).unwrap(),
+        "os\n",
+        "The shebang was ignored"
+    );
+    let wechsel_log = log_file_path(&get_state_dir().expect("Could not find state dir"));
+    assert!(
+        fs::read_to_string(wechsel_log)
+            .unwrap()
+            .contains("10-awk: awk says hi"),
+        "The output of the hook is not in the log"
+    );
+
+    // The hook and everything it started are stopped after the timeout of the config
+    let output = call_as_user(
+        &[
+            PATH_TO_WECHSEL_BINARY,
+            "config",
+            "set",
+            "hooks.timeout",
+            "1",
+        ],
+        home_dir,
+    );
+    assert!(output.status.success());
+    write_script(
+        &path_from_iter([&dir, &PathBuf::from("20-hang")]),
+        &format!("#!/bin/sh\nsleep 30 &\necho $! > {pid_file:?}\nwait\n"),
+    );
+    let start = std::time::Instant::now();
+    assert_eq!(change("ml"), Some(8), "The timed out hook was not reported");
+    assert!(
+        start.elapsed().as_secs() < 10,
+        "The timeout was not applied"
+    );
+    let pid = fs::read_to_string(&pid_file).unwrap();
+    // A killed process can stay a zombie for a moment
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid.trim())).unwrap_or_default();
+    let running = stat
+        .rsplit(") ")
+        .next()
+        .is_some_and(|rest| !rest.starts_with('Z'));
+    assert!(
+        stat.is_empty() || !running,
+        "A process of the hook is still running"
+    );
+
+    let output = call_as_user(
+        &[
+            PATH_TO_WECHSEL_BINARY,
+            "config",
+            "set",
+            "hooks.timeout",
+            "0",
+        ],
+        home_dir,
+    );
+    assert!(output.status.success());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+// The scripts in on-prj-change.d run in lexical order with the settings of their headers
+fn hook_dir_test(home_dir: &PathBuf) {
+    println!("-- on-prj-change.d --");
+    let log = &path_from_iter([home_dir, &PathBuf::from("hook_dir.log")]);
+    let dir = path_from_iter([
+        get_config_dir().expect("Could not find config dir"),
+        PathBuf::from("on-prj-change.d"),
+    ]);
+    fs::create_dir_all(&dir).unwrap();
+    let hook = |name: &str, header: &str, content: &str| {
+        write_script(
+            &path_from_iter([&dir, &PathBuf::from(name)]),
+            &format!("#!/bin/sh\n{header}\necho {name} >> {log:?}\n{content}\n"),
+        )
+    };
+    let change = |prj: &str| {
+        let _ = fs::remove_file(log);
+        let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "change", prj, "--json"], home_dir);
+        let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let calls = fs::read_to_string(log).unwrap_or_default();
+        (output.status.code(), json, calls)
+    };
+    hook("20-second", "# wechsel: continue-on-failure", "exit 1");
+    // What hooks print doesn't get into the json on stdout
+    hook("10-first", "", "echo printed by 10-first");
+    hook("30-third", "", "");
+    hook(".hidden", "", "");
+
+    let (code, json, calls) = change("os");
+    assert_eq!(
+        code,
+        Some(0),
+        "A hook that continues on failure failed the change"
+    );
+    assert_eq!(
+        calls, "10-first\n20-second\n30-third\n",
+        "Unexpected order of the hooks"
+    );
+    let exit_codes: Vec<_> = dir_runs(&json)
+        .iter()
+        .map(|run| run["exit_code"].as_i64())
+        .collect();
+    assert_eq!(exit_codes, [Some(0), Some(1), Some(0)]);
+    assert_eq!(dir_runs(&json)[0]["stdout"], "printed by 10-first\n");
+
+    // Without continue-on-failure the hooks after a failing one don't run
+    hook("20-second", "", "exit 3");
+    let (code, json, calls) = change("ml");
+    assert_eq!(code, Some(8), "The failing hook was not reported");
+    assert_eq!(json["error"]["kind"], "hook_failed");
+    assert_eq!(calls, "10-first\n20-second\n");
+
+    hook("20-second", "# wechsel: timeout=0.2", "sleep 5");
+    let start = std::time::Instant::now();
+    let (code, _, calls) = change("os");
+    assert!(start.elapsed().as_secs() < 4, "The timeout was not applied");
+    assert_eq!(code, Some(8), "The timed out hook was not reported");
+    assert_eq!(calls, "10-first\n20-second\n");
+
+    // Hooks run in their own process group, Ctrl-C still reaches them and then wechsel
+    hook(
+        "20-second",
+        "",
+        &format!("trap 'echo interrupted >> {log:?}; exit 130' INT\nwhile :; do sleep 0.1; done"),
+    );
+    let _ = fs::remove_file(log);
+    let start = std::time::Instant::now();
+    let mut child = Command::new(PATH_TO_WECHSEL_BINARY)
+        .args(["change", "os"])
+        .current_dir(home_dir)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    while !fs::read_to_string(log).is_ok_and(|calls| calls.contains("20-second")) {
+        thread::sleep(Duration::from_millis(20));
+    }
+    thread::sleep(Duration::from_millis(200));
+    let interrupt = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(interrupt.success());
+    let status = child.wait().unwrap();
+    assert!(start.elapsed().as_secs() < 4, "The hook wasn't interrupted");
+    assert_eq!(status.signal(), Some(2), "wechsel didn't stop on Ctrl-C");
+    assert_eq!(
+        fs::read_to_string(log).unwrap(),
+        "10-first\n20-second\ninterrupted\n",
+        "The interrupt didn't reach the hook or the hooks after it ran"
+    );
+
+    hook("20-second", "# wechsel: background", "sleep 2");
+    let start = std::time::Instant::now();
+    let (code, json, calls) = change("ml");
+    assert!(
+        start.elapsed().as_secs() < 2,
+        "The background hook was waited for"
+    );
+    assert_eq!(code, Some(0));
+    // The background hook can log at any time
+    assert!(calls.starts_with("10-first\n") && calls.contains("30-third\n"));
+    assert_eq!(dir_runs(&json)[1]["background"], true);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+// The runs of the hooks in on-prj-change.d, without the ones of the projects
+fn dir_runs(json: &serde_json::Value) -> Vec<serde_json::Value> {
+    json["result"]["hooks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|run| run["hook"].as_str().unwrap().contains("on-prj-change.d"))
+        .cloned()
+        .collect()
+}
+
+// A failing pre-change hook blocks the change before any link or hook is touched
+fn veto_test(home_dir: &PathBuf, uni: &Path, log: &Path) {
+    println!("-- pre-change --");
+    let change = |prj: &str, force: bool| {
+        let mut args = vec![PATH_TO_WECHSEL_BINARY, "change", prj, "--json"];
+        if force {
+            args.push("--force");
+        }
+        call_as_user(&args, home_dir)
+    };
+    let link = path_from_iter([home_dir, &PathBuf::from(CURRENT_PROJECT_FOLDER)]);
+    assert!(change("ml", false).status.success());
+    let ml = fs::read_link(&link).unwrap();
+
+    // Only the hooks of the projects that are left run, uni stays active
+    write_script(
+        &path_from_iter([uni, Path::new(PRE_CHANGE_HOOK)]),
+        "echo \"$HOOK_PRJ has uncommitted work\" >&2\nexit 1\n",
+    );
+    allow_test("uni");
+    assert!(change("os", false).status.success());
+    assert!(change("ml", false).status.success());
+
+    let _ = fs::remove_file(log);
+    let output = change("home", false);
+    assert_eq!(output.status.code(), Some(10), "The change was not vetoed");
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["error"]["kind"], "vetoed");
+    assert!(
+        json["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("uni has uncommitted work"),
+        "The stderr of the hook is not shown"
+    );
+    assert_eq!(fs::read_link(&link).unwrap(), ml, "The link was changed");
+    assert!(!log.exists(), "The leave hooks ran anyway");
+
+    assert!(change("home", true).status.success());
+    assert_ne!(fs::read_link(&link).unwrap(), ml);
+    fs::remove_file(path_from_iter([uni, Path::new(PRE_CHANGE_HOOK)])).unwrap();
+
+    // The global pre-change hook runs for every change
+    let global = path_from_iter([
+        get_config_dir().expect("Could not find config dir"),
+        PathBuf::from("pre-change"),
+    ]);
+    write_script(&global, "[ \"$PRJ\" != os ]\n");
+    let output = change("os", false);
+    assert_eq!(output.status.code(), Some(10), "The change was not vetoed");
+    assert!(change("ml", false).status.success());
+    fs::remove_file(global).unwrap();
+}
+
+fn new_nested(name: &str, parent: &str, parent_path: &Path, home_dir: &PathBuf) -> PathBuf {
+    println!("-- new: {name} --");
+    let output = call_as_user(
+        &[
+            PATH_TO_WECHSEL_BINARY,
+            "new",
+            name,
+            "-p",
+            parent,
+            "--folders",
+            "Desktop",
+        ],
+        home_dir,
+    );
+    assert!(output.status.success(), "wechsel new {name} failed");
+    path_from_iter([parent_path, Path::new(name)]).with_extension(PROJECT_EXTENSION)
+}
+
+// Each hook appends what it was called for to the log
+fn write_hook(prj_path: &Path, hook: &str, action: &str, log: &Path) {
+    write_script(
+        &path_from_iter([prj_path, Path::new(hook)]),
+        &format!("echo \"{action} $HOOK_PRJ $OLD_CHAIN $NEW_CHAIN\" >> {log:?}\n"),
+    );
+}
+
+fn write_script(path: &Path, content: &str) {
+    fs::write(path, content).unwrap();
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+fn enter_leave_test(prj_name: &str, log: &Path, expected: &[&str]) {
+    println!("-- enter/leave: {prj_name} --");
+    let _ = fs::remove_file(log);
+    let output = call_as_user(
+        &[PATH_TO_WECHSEL_BINARY, "change", prj_name],
+        &home_dir().expect("could not find home dir"),
+    );
+    print_command_output(output);
+    let content = fs::read_to_string(log).unwrap_or_default();
+    let calls: Vec<&str> = content.lines().collect();
+    assert_eq!(calls, expected, "Unexpected enter and leave hooks");
+}
@@ -0,0 +1,847 @@
+use std::{
+    env, fs,
+    os::unix::fs::{MetadataExt, symlink},
+    path::{Path, PathBuf},
+};
+
+use rand::random_bool;
+use wechsel::home_dir;
+
+use crate::utils::*;
+
+mod auto;
+mod dbus;
+mod hooks;
+mod library;
+mod migration;
+mod report;
+mod utils;
+mod watch;
+
+use wechsel::{
+    CURRENT_PROJECT_FOLDER, DEFAULT_ROOT_PRJ, HOME_ENV, HOME_FOLDERS, PROJECT_EXTENSION,
+    ProjectTreeNode, TREE_SCHEMA_VERSION, TreeOutput, WECHSEL_FOLDER_EXTENSION, bashrc_path,
+    config_file_path, fish_config_path, get_config_dir, get_environment_vars_fish_path,
+    get_environment_vars_path, get_home_folder_paths, get_state_dir, history_path, hook_env_path,
+    layout_version_path, log_file_path, on_prj_change_path, path_from_iter, time_log_path,
+};
+/// Runs every scenario against the `wechsel` binary, in a temporary home folder unless `WECHSEL_HOME` is set
+pub fn run_scenarios() {
+    println!("Starting Wechsel Testing");
+    let sandbox = sandbox();
+    println!("-------- Test 1 --------");
+    test1();
+    println!("-------- Test 2 --------");
+    test2();
+    println!("-------- Test Report --------");
+    report::report_test();
+    println!("-------- Test Auto --------");
+    auto::auto_test();
+    println!("-------- Test Watch --------");
+    watch::watch_test();
+    println!("-------- Test Migration --------");
+    migration::migration_test();
+    println!("-------- Test Hooks --------");
+    hooks::hooks_test();
+    println!("-------- Test Library --------");
+    library::library_test();
+    if dbus::dbus_available() {
+        println!("-------- Test D-Bus --------");
+        test_dbus();
+    }
+    if let Some(sandbox) = sandbox {
+        let _ = fs::remove_dir_all(sandbox);
+    }
+    println!("-------- Done --------");
+}
+
+// Runs the tests in a temporary home folder, unless WECHSEL_HOME already points to one
+fn sandbox() -> Option<PathBuf> {
+    // Prefer the wechsel binary that was built together with the tests,
+    // `cargo test` runs them from the deps folder below it
+    if let Some(dir) = env::current_exe().ok().and_then(|exe| {
+        exe.ancestors()
+            .skip(1)
+            .take(2)
+            .find(|dir| dir.join(PATH_TO_WECHSEL_BINARY).is_file())
+            .map(PathBuf::from)
+    }) {
+        let path = env::var_os("PATH").unwrap_or_default();
+        let paths = std::iter::once(dir).chain(env::split_paths(&path).collect::<Vec<_>>());
+        env::set_var("PATH", env::join_paths(paths).unwrap());
+    }
+
+    if env::var_os(HOME_ENV).is_some() {
+        return None;
+    }
+    let home = env::temp_dir().join(format!("wechsel_testing_{}", std::process::id()));
+    // Like a real home folder, with the files the shell integration gets added to
+    fs::create_dir_all(home.join(".config/fish")).unwrap();
+    fs::write(home.join(".bashrc"), "").unwrap();
+    env::set_var(HOME_ENV, &home);
+    println!("Running in {home:?}");
+    Some(home)
+}
+
+pub(crate) struct Project {
+    pub name: String,
+    pub path: PathBuf,
+    // These are the paths to the folders in the home dir that this project has wechsel folders for
+    pub folders: Vec<PathBuf>,
+    // This is a list of all wechsel folders this prj or any parent has, so all folders that could change if this project gets wechseled
+    pub all_relevant_folders: Vec<PathBuf>,
+}
+fn test1() {
+    security_check();
+    let home_dir = home_dir().expect("could not find home dir");
+
+    setup_home(&home_dir, true);
+    init_test();
+    init_test();
+}
+
+fn test2() {
+    security_check();
+
+    let home_dir = home_dir().expect("could not find home dir");
+
+    setup_home(&home_dir, true);
+    assert!(
+        get_current_tree(false).is_none(),
+        "Wechsel tree should error if not initialized"
+    );
+    let home_prj = init_test();
+    let prj1 = new_test("prj1", &home_prj);
+
+    setup_on_change_test(&prj1.path);
+    allow_test(&prj1.name);
+    change_test(&prj1);
+    assert_prj_on_change_test(&prj1);
+
+    setup_on_change_test(&home_prj.path);
+    allow_test(&home_prj.name);
+    change_test(&home_prj);
+    assert_prj_on_change_test(&home_prj);
+
+    let new_destination = path_from_iter([&home_dir, &PathBuf::from("test_prj")]);
+    let old_destination = &prj1.path;
+    fs::rename(old_destination, &new_destination).unwrap();
+    symlink(new_destination, old_destination).unwrap();
+
+    let new_destination = path_from_iter([&home_dir, &PathBuf::from("test_wechsel_folder")]);
+    let old_destination = path_from_iter([
+        &prj1.path,
+        &PathBuf::from(prj1.folders[0].file_name().unwrap()),
+    ])
+    .with_extension(WECHSEL_FOLDER_EXTENSION);
+    fs::rename(&old_destination, &new_destination).unwrap();
+    symlink(new_destination, &old_destination).unwrap();
+
+    change_test(&prj1);
+    assert_prj_on_change_test(&prj1);
+
+    let tree = get_current_tree(true).unwrap();
+    assert!(tree.tree.find(&prj1.name).is_some());
+    schema_test();
+
+    conflict_test(&prj1);
+    doctor_test(&prj1);
+    cd_hook_test(&home_prj, &prj1);
+    resolve_test(&home_prj, &prj1);
+    config_test();
+}
+
+// Settings without a default are listed and typed by the config schema, not by their value
+fn config_test() {
+    println!("-- config --");
+    let home_dir = home_dir().expect("could not find home dir");
+    let config = config_file_path(&get_config_dir().expect("could not find config dir"));
+    let previous_config = fs::read_to_string(&config).ok();
+    let config_cmd = |args: &[&str]| {
+        call_as_user(
+            &[&[PATH_TO_WECHSEL_BINARY, "config"], args].concat(),
+            &home_dir,
+        )
+    };
+
+    let output = config_cmd(&["list"]);
+    let list = String::from_utf8(output.stdout).unwrap();
+    for key in [
+        "hooks.on_prj_change",
+        "hooks.on_prj_create",
+        "hooks.pre_change",
+    ] {
+        assert!(
+            list.contains(&format!("{key} = (not set)")),
+            "config list doesn't show that {key} is not set:\n{list}"
+        );
+        let output = config_cmd(&["get", key]);
+        assert!(output.status.success(), "config get {key} failed");
+        assert!(
+            output.stdout.is_empty(),
+            "config get printed a value for {key}"
+        );
+    }
+    let output = call_as_user(
+        &[
+            PATH_TO_WECHSEL_BINARY,
+            "--json",
+            "config",
+            "get",
+            "hooks.pre_change",
+        ],
+        &home_dir,
+    );
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(
+        output["result"].is_null(),
+        "An unset setting has a value: {output}"
+    );
+
+    // A file name that looks like a number is still a file name
+    assert!(
+        config_cmd(&["set", "hooks.pre_change", "5"])
+            .status
+            .success()
+    );
+    let output = call_as_user(
+        &[
+            PATH_TO_WECHSEL_BINARY,
+            "--json",
+            "config",
+            "get",
+            "hooks.pre_change",
+        ],
+        &home_dir,
+    );
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(output["result"], "5");
+    assert!(
+        config_cmd(&["set", "links.conflict", "merge"])
+            .status
+            .success()
+    );
+    assert_eq!(config_cmd(&["get", "hooks.nope"]).status.code(), Some(3));
+
+    match previous_config {
+        Some(previous_config) => fs::write(&config, previous_config).unwrap(),
+        None => fs::remove_file(&config).unwrap(),
+    }
+}
+
+// Paths and `-` in the place of a project name
+fn resolve_test(home_prj: &Project, prj: &Project) {
+    println!("-- resolve --");
+    let home_dir = home_dir().expect("could not find home dir");
+    let change = |target: &str, dir: &PathBuf| {
+        call_as_user(&[PATH_TO_WECHSEL_BINARY, "change", target], dir)
+    };
+    let active = || get_current_tree(false).unwrap().active;
+    let folder = prj.path.join("resolve-sub");
+    fs::create_dir_all(&folder).unwrap();
+
+    for (target, dir) in [
+        (".", &prj.path),
+        ("..", &folder),
+        (folder.to_str().unwrap(), &home_dir),
+        (
+            &format!("{}/", prj.path.strip_prefix(&home_dir).unwrap().display()),
+            &home_dir,
+        ),
+    ] {
+        assert!(change(&home_prj.name, &home_dir).status.success());
+        let output = change(target, dir);
+        assert!(output.status.success(), "change {target} in {dir:?} failed");
+        assert_eq!(
+            active(),
+            prj.name,
+            "{target} in {dir:?} is not {}",
+            prj.name
+        );
+    }
+
+    // `-` is the project that was active before
+    assert!(change(&home_prj.name, &home_dir).status.success());
+    assert!(change("-", &home_dir).status.success());
+    assert_eq!(active(), prj.name);
+    assert!(change("-", &home_dir).status.success());
+    assert_eq!(active(), home_prj.name);
+
+    // Paths outside of the project tree are not found
+    let stray = home_dir.join("resolve-outside/stray.p");
+    fs::create_dir_all(&stray).unwrap();
+    for target in [
+        ".",
+        stray.to_str().unwrap(),
+        home_dir.join("missing/folder").to_str().unwrap(),
+    ] {
+        let output = change(target, &home_dir);
+        assert_eq!(output.status.code(), Some(3), "{target} was found");
+    }
+    assert_eq!(active(), home_prj.name);
+    fs::remove_dir_all(home_dir.join("resolve-outside")).unwrap();
+    fs::remove_dir(folder).unwrap();
+
+    // Names aren't unique, a path always means the project in that folder
+    let twins = add_twins(home_prj, prj);
+    for twin in twins.iter() {
+        let output = change(".", twin);
+        assert!(output.status.success(), "change . in {twin:?} failed");
+        assert_eq!(active_folder(), *twin);
+    }
+    assert_eq!(change("twin", &home_dir).status.code(), Some(4));
+    assert!(change(&home_prj.name, &home_dir).status.success());
+    remove_twins(twins);
+}
+
+// Two projects named twin, one in each of the projects
+fn add_twins(home_prj: &Project, prj: &Project) -> [PathBuf; 2] {
+    let twins = [&home_prj.path, &prj.path].map(|parent| {
+        path_from_iter([parent, &PathBuf::from("twin")]).with_extension(PROJECT_EXTENSION)
+    });
+    for twin in twins.iter() {
+        fs::create_dir_all(twin).unwrap();
+    }
+    twins
+}
+
+fn remove_twins(twins: [PathBuf; 2]) {
+    for twin in twins {
+        fs::remove_dir(twin).unwrap();
+    }
+}
+
+// Where ~/Project points to
+fn active_folder() -> PathBuf {
+    let home_dir = home_dir().expect("could not find home dir");
+    fs::read_link(path_from_iter([
+        &home_dir,
+        &PathBuf::from(CURRENT_PROJECT_FOLDER),
+    ]))
+    .unwrap()
+}
+
+fn cd_hook_test(home_prj: &Project, prj: &Project) {
+    println!("-- cd-hook --");
+    let home_dir = home_dir().expect("could not find home dir");
+    let call = |args: &[&str]| {
+        let output = call_as_user(&[&[PATH_TO_WECHSEL_BINARY], args].concat(), &home_dir);
+        assert!(output.status.success(), "wechsel {args:?} failed");
+    };
+    let cd = |path: &Path| call(&["cd-hook", path.to_str().unwrap()]);
+    let active = || get_current_tree(false).unwrap().active;
+
+    call(&["change", &home_prj.name]);
+    cd(&prj.path);
+    assert_eq!(active(), prj.name, "cd-hook didn't switch to the project");
+
+    // Leaving the project folder and coming back switches again
+    call(&["change", &home_prj.name]);
+    cd(&home_dir);
+    cd(&prj.path.join(prj.folders[0].file_name().unwrap()));
+    assert_eq!(
+        active(),
+        prj.name,
+        "cd-hook didn't switch after coming back"
+    );
+
+    // So does coming back to it after another project was made active
+    call(&["change", &home_prj.name]);
+    cd(&prj.path);
+    assert_eq!(
+        active(),
+        prj.name,
+        "cd-hook ignored the change of the active project"
+    );
+
+    // Projects with the marker are left alone
+    let marker = prj.path.join(".no-auto-switch");
+    fs::write(&marker, "").unwrap();
+    call(&["change", &home_prj.name]);
+    cd(&home_dir);
+    cd(&prj.path);
+    assert_eq!(active(), home_prj.name, "cd-hook ignored .no-auto-switch");
+    fs::remove_file(marker).unwrap();
+
+    // A project folder outside of the project tree isn't switched to
+    let outside = home_dir.join("cd-hook-outside/stray.p");
+    fs::create_dir_all(&outside).unwrap();
+    cd(&outside);
+    assert_eq!(active(), home_prj.name);
+    fs::remove_dir_all(home_dir.join("cd-hook-outside")).unwrap();
+
+    // Of two projects with the same name the one of the folder is switched to
+    let twins = add_twins(home_prj, prj);
+    for twin in twins.iter() {
+        cd(twin);
+        assert_eq!(active_folder(), *twin, "cd-hook didn't switch to {twin:?}");
+    }
+    call(&["change", &home_prj.name]);
+    cd(&home_dir);
+    remove_twins(twins);
+}
+
+fn test_dbus() {
+    security_check();
+
+    let home_dir = home_dir().expect("could not find home dir");
+
+    setup_home(&home_dir, true);
+    let home_prj = init_test();
+    let prj1 = new_test("prj1", &home_prj);
+    dbus::dbus_test(&home_prj, &prj1);
+}
+
+pub(crate) fn init_test() -> Project {
+    println!("-- Init --");
+    let home_dir = home_dir().expect("could not find home dir");
+
+    let before = query_folder(&home_dir);
+
+    let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "init", "-y"], &home_dir);
+    print_command_output(output);
+
+    let after = query_folder(&home_dir);
+
+    // The time log, the log file and the state folders leading up to them
+    let state_dir = get_state_dir().expect("Could not find state dir");
+    let state_files = state_dir
+        .ancestors()
+        .take_while(|path| path != &home_dir)
+        .map(PathBuf::from)
+        .chain([time_log_path(&state_dir), log_file_path(&state_dir)])
+        .collect::<Vec<_>>();
+
+    let home_prj = path_from_iter([
+        &home_dir,
+        &PathBuf::from(DEFAULT_ROOT_PRJ).with_extension(PROJECT_EXTENSION),
+    ]);
+    let config_dir = get_config_dir().expect("Could not find config dir");
+    assert_included_symlinks(
+        after.iter(),
+        get_home_folder_paths()
+            .map(|(_, path)| path)
+            .chain([path_from_iter([
+                &home_dir,
+                &PathBuf::from(CURRENT_PROJECT_FOLDER),
+            ])]),
+    );
+    assert_included(
+        after.iter(),
+        [
+            config_dir.clone(),
+            on_prj_change_path(&config_dir),
+            home_prj.clone(),
+        ],
+    );
+
+    assert_includes_nothing_other_then(
+        after.difference(&before),
+        get_home_folder_paths()
+            .map(|(_, path)| path)
+            .chain([
+                home_prj.clone(),
+                config_dir.clone(),
+                path_from_iter([&home_dir, &PathBuf::from(CURRENT_PROJECT_FOLDER)]),
+                fish_config_path(&config_dir),
+                bashrc_path(),
+                on_prj_change_path(&config_dir),
+                get_environment_vars_fish_path(&config_dir),
+                get_environment_vars_path(&config_dir),
+                hook_env_path(&config_dir),
+                history_path(&config_dir),
+                layout_version_path(&config_dir),
+                path_from_iter(["/root", ".cache"]),
+            ])
+            .chain(state_files),
+        "init",
+    );
+
+    let tree = get_current_tree(true).unwrap();
+    assert!(tree.tree.find(DEFAULT_ROOT_PRJ).is_some());
+    assert!(tree.active == DEFAULT_ROOT_PRJ);
+
+    Project {
+        path: home_prj,
+        name: DEFAULT_ROOT_PRJ.to_string(),
+        folders: get_home_folder_paths().map(|(_, p)| p).collect(),
+        all_relevant_folders: get_home_folder_paths().map(|(_, p)| p).collect(),
+    }
+}
+
+fn new_test(name: &str, parent: &Project) -> Project {
+    println!("-- new: {name} --");
+
+    let home_dir = home_dir().expect("could not find home dir");
+
+    let before = query_folder(&home_dir);
+
+    let folder_list: &[&str] = &HOME_FOLDERS
+        .into_iter()
+        .filter(|_| random_bool(0.8))
+        .collect::<Vec<_>>();
+    let output = call_as_user(
+        &[
+            PATH_TO_WECHSEL_BINARY,
+            "new",
+            name,
+            "-p",
+            parent.name.as_str(),
+            "--folders",
+            &folder_list.join(" "),
+        ],
+        &home_dir,
+    );
+
+    print_command_output(output);
+
+    let after = query_folder(&home_dir);
+
+    let root_prj_path = path_from_iter([&home_dir, &PathBuf::from(DEFAULT_ROOT_PRJ)])
+        .with_extension(PROJECT_EXTENSION);
+
+    let new_prj_path =
+        path_from_iter([&root_prj_path, &PathBuf::from(name)]).with_extension(PROJECT_EXTENSION);
+
+    let folders: Vec<PathBuf> = folder_list
+        .iter()
+        .map(|name| path_from_iter([&home_dir, &PathBuf::from(name)]))
+        .collect();
+
+    let new_prj = Project {
+        name: name.to_string(),
+        path: new_prj_path.clone(),
+        folders: folders.clone(),
+        all_relevant_folders: folders
+            .into_iter()
+            .chain(parent.all_relevant_folders.iter().cloned())
+            .collect(),
+    };
+
+    let folders = new_prj
+        .all_relevant_folders
+        .iter()
+        .cloned()
+        .chain([
+            new_prj_path.clone(),
+            path_from_iter([&home_dir, &PathBuf::from(CURRENT_PROJECT_FOLDER)]),
+        ])
+        .chain(folder_list.iter().map(|name| {
+            path_from_iter([&new_prj_path, &PathBuf::from(name)])
+                .with_extension(WECHSEL_FOLDER_EXTENSION)
+        }));
+    assert_included(after.iter(), folders.clone());
+    assert_includes_nothing_other_then(after.difference(&before), folders, "new");
+
+    let tree = get_current_tree(true).unwrap();
+    assert!(tree.tree.find(name).is_some());
+    assert!(tree.active == name);
+
+    new_prj
+}
+
+pub(crate) fn change_test(prj: &Project) {
+    println!("-- change1: {} --", prj.name);
+    let home_dir = home_dir().expect("could not find home dir");
+    let config_dir = get_config_dir().expect("Could not find config dir");
+    let previous = get_current_tree(false)
+        .map(|tree| tree.active)
+        .unwrap_or_default();
+
+    let before = query_folder(&home_dir);
+
+    let output = call_as_user(
+        &[PATH_TO_WECHSEL_BINARY, "change", prj.name.as_str()],
+        &home_dir,
+    );
+    print_command_output(output);
+
+    let after = query_folder(&home_dir);
+
+    let tree = get_current_tree(true).unwrap();
+    assert!(tree.active == prj.name);
+
+    assert_included(
+        after.iter(),
+        prj.all_relevant_folders
+            .clone()
+            .into_iter()
+            .chain([path_from_iter([
+                &home_dir,
+                &PathBuf::from(CURRENT_PROJECT_FOLDER),
+            ])]),
+    );
+    assert_includes_nothing_other_then(
+        after.difference(&before),
+        prj.all_relevant_folders.clone().into_iter().chain([
+            path_from_iter([&home_dir, &PathBuf::from(CURRENT_PROJECT_FOLDER)]),
+            get_environment_vars_fish_path(&config_dir),
+            get_environment_vars_path(&config_dir),
+            hook_env_path(&config_dir),
+        ]),
+        "change",
+    );
+
+    // Assert that the wechsel folders of the target project got symlinked correctly
+    for folder in prj.folders.iter() {
+        if let Ok(folder_meta) = folder.metadata() {
+            let folder_target =
+                path_from_iter([&prj.path, &PathBuf::from(folder.file_name().unwrap())])
+                    .with_extension(WECHSEL_FOLDER_EXTENSION);
+            if let Ok(target_meta) = folder_target.metadata() {
+                assert!(
+                    folder_meta.dev() == target_meta.dev()
+                        && folder_meta.ino() == target_meta.ino(),
+                    "A wechsel folder ({folder_target:?}) was not correctly symlinked"
+                )
+            } else {
+                panic!("Err 12 {folder_target:?}")
+            }
+        } else {
+            panic!("Err 122")
+        }
+    }
+    //Assert that ~/Project is symlinked correctly
+    assert!(
+        path_from_iter([&home_dir, &PathBuf::from(CURRENT_PROJECT_FOLDER)])
+            .metadata()
+            .and_then(|meta| prj.path.metadata().map(|target_meta| (meta, target_meta)))
+            .map(|(m1, m2)| m1.dev() == m2.dev() && m1.ino() == m2.ino())
+            .unwrap_or(false),
+        "~/{CURRENT_PROJECT_FOLDER} is not symlinked correctly to {:?}",
+        &prj.path
+    );
+
+    navigation_test(prj, &previous);
+}
+
+// Walks away from prj with back/forward/up/down and always ends up on prj again
+fn navigation_test(prj: &Project, previous: &str) {
+    println!("-- navigation: {} --", prj.name);
+    let home_dir = home_dir().expect("could not find home dir");
+    let navigate = |cmd: &str| {
+        let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, cmd], &home_dir);
+        print_command_output(output.clone());
+        output
+    };
+    let assert_active = |expected: &str, after: &str| {
+        let active = get_current_tree(true).unwrap().active;
+        assert_eq!(active, expected, "{after} activated the wrong project");
+    };
+
+    if previous != prj.name {
+        let output = call_as_user(
+            &[PATH_TO_WECHSEL_BINARY, "--json", "history", "-n", "1"],
+            &home_dir,
+        );
+        let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let last = &output["result"][0];
+        assert_eq!(last["from"], previous, "history has the wrong origin");
+        assert_eq!(last["to"], prj.name.as_str(), "history misses the change");
+    }
+
+    if !previous.is_empty() && previous != prj.name {
+        navigate("back");
+        assert_active(previous, "back");
+        navigate("forward");
+        assert_active(&prj.name, "forward");
+    }
+
+    let tree = get_current_tree(false).unwrap().tree;
+    match find_parent(&tree, &prj.name) {
+        Some(parent) => {
+            navigate("up");
+            assert_active(&parent, "up");
+            navigate("back");
+            assert_active(&prj.name, "back after up");
+        }
+        None => assert_eq!(navigate("up").status.code(), Some(3), "up found a parent"),
+    }
+
+    let children = tree
+        .find(&prj.name)
+        .map(|node| node.children)
+        .unwrap_or_default();
+    match children.as_slice() {
+        [] => assert_eq!(
+            navigate("down").status.code(),
+            Some(3),
+            "down found a child"
+        ),
+        [child] => {
+            navigate("down");
+            assert_active(&child.prj_name, "down");
+            navigate("back");
+            assert_active(&prj.name, "back after down");
+        }
+        // down asks which child to go to, which needs a terminal
+        _ => {}
+    }
+}
+
+fn find_parent(node: &ProjectTreeNode, prj_name: &str) -> Option<String> {
+    node.children.iter().find_map(|child| {
+        if child.prj_name == prj_name {
+            Some(node.prj_name.clone())
+        } else {
+            find_parent(child, prj_name)
+        }
+    })
+}
+
+fn conflict_test(prj: &Project) {
+    println!("-- conflict --");
+    let home_dir = home_dir().expect("could not find home dir");
+
+    // A real folder with a file in the place of a link
+    let folder = &prj.all_relevant_folders[0];
+    let target = fs::canonicalize(folder).unwrap();
+    fs::remove_file(folder).unwrap();
+    fs::create_dir(folder).unwrap();
+    let file_name = "wechsel-conflict-test";
+    fs::write(folder.join(file_name), "").unwrap();
+
+    let output = call_as_user(
+        &[
+            PATH_TO_WECHSEL_BINARY,
+            "change",
+            prj.name.as_str(),
+            "--on-conflict",
+            "merge",
+        ],
+        &home_dir,
+    );
+    print_command_output(output);
+    assert!(
+        folder.is_symlink(),
+        "The real folder {folder:?} was not replaced by a link"
+    );
+    assert!(
+        target.join(file_name).is_file(),
+        "The contents of {folder:?} were not moved to {target:?}"
+    );
+    fs::remove_file(target.join(file_name)).unwrap();
+
+    // Stashing moves the folder aside instead
+    fs::remove_file(folder).unwrap();
+    fs::create_dir(folder).unwrap();
+    fs::write(folder.join(file_name), "").unwrap();
+    let output = call_as_user(
+        &[
+            PATH_TO_WECHSEL_BINARY,
+            "change",
+            prj.name.as_str(),
+            "--on-conflict",
+            "stash",
+        ],
+        &home_dir,
+    );
+    print_command_output(output);
+    assert!(
+        folder.is_symlink(),
+        "The real folder {folder:?} was not replaced by a link"
+    );
+    let backup_prefix = format!("{}.backup-", folder.file_name().unwrap().to_string_lossy());
+    let backup = fs::read_dir(folder.parent().unwrap())
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            path.file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with(&backup_prefix)
+        })
+        .expect("The real folder was not stashed");
+    assert!(backup.join(file_name).is_file());
+    assert!(!target.join(file_name).exists(), "The stash was merged");
+    fs::remove_dir_all(backup).unwrap();
+}
+
+fn doctor_test(prj: &Project) {
+    println!("-- doctor --");
+    let home_dir = home_dir().expect("could not find home dir");
+
+    // Replace a link with an empty folder, like an application that recreated it
+    let folder = &prj.all_relevant_folders[0];
+    fs::remove_file(folder).unwrap();
+    fs::create_dir(folder).unwrap();
+
+    let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "doctor", "--fix"], &home_dir);
+    print_command_output(output);
+    assert!(
+        folder.is_symlink(),
+        "wechsel doctor --fix did not replace the empty folder {folder:?} with a link"
+    );
+
+    // A missing ~/Project is left to `wechsel change`, which runs the hooks
+    let active_link = home_dir.join(CURRENT_PROJECT_FOLDER);
+    let active = fs::read_link(&active_link).unwrap();
+    fs::remove_file(&active_link).unwrap();
+    let output = call_as_user(
+        &[PATH_TO_WECHSEL_BINARY, "doctor", "--fix", "--json"],
+        &home_dir,
+    );
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(
+        !active_link.is_symlink(),
+        "wechsel doctor --fix linked {active_link:?} without changing the project"
+    );
+    assert!(
+        output["result"].as_array().unwrap().iter().any(|problem| {
+            problem["message"]
+                .as_str()
+                .unwrap()
+                .contains("run `wechsel change")
+        }),
+        "wechsel doctor didn't point to wechsel change: {output}"
+    );
+    std::os::unix::fs::symlink(active, &active_link).unwrap();
+
+    let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "doctor", "--json"], &home_dir);
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(
+        output["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|problem| problem["severity"] == "info"),
+        "wechsel doctor found problems after --fix: {output}"
+    );
+}
+
+pub(crate) fn get_current_tree(print: bool) -> Option<TreeOutput> {
+    let output = call_as_user(
+        &[PATH_TO_WECHSEL_BINARY, "tree"],
+        &home_dir().expect("could not find home dir"),
+    );
+    if print && !output.status.success() && !output.stderr.is_empty() {
+        println!("{}", String::from_utf8(output.stderr).unwrap());
+        return None;
+    }
+    serde_json::from_slice::<TreeOutput>(&output.stdout).ok()
+}
+
+// The schema in the repository has to match the one generated from the types
+fn schema_test() {
+    println!("-- schema --");
+    let output = call_as_user(
+        &[PATH_TO_WECHSEL_BINARY, "tree", "--schema"],
+        &home_dir().expect("could not find home dir"),
+    );
+    let schema: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let published: serde_json::Value = serde_json::from_str(include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../schema/tree.schema.json"
+    )))
+    .unwrap();
+    assert_eq!(
+        schema, published,
+        "schema/tree.schema.json is outdated, regenerate it with `wechsel tree --schema`"
+    );
+
+    let tree = get_current_tree(true).unwrap();
+    assert_eq!(tree.schema_version, TREE_SCHEMA_VERSION);
+    let value = serde_json::to_value(&tree).unwrap();
+    let parsed: TreeOutput = serde_json::from_value(value.clone()).unwrap();
+    assert_eq!(serde_json::to_value(parsed).unwrap(), value);
+}
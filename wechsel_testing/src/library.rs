@@ -0,0 +1,157 @@
+use std::{env, fs, os::unix::fs::PermissionsExt, path::PathBuf};
+
+use wechsel::{
+    CURRENT_PROJECT_FOLDER, ChangeOptions, HookProject, LifecycleEvent, PROJECT_EXTENSION,
+    WECHSEL_FOLDER_EXTENSION, WechselError, Workspace, home_dir, path_from_iter,
+};
+
+use crate::utils::*;
+
+// Uses the library on a home folder of its own, without going through the environment
+pub(crate) fn library_test() {
+    security_check();
+    let home = env::temp_dir().join(format!("wechsel_library_{}", std::process::id()));
+    let config_dir = home.join("config");
+    let _ = fs::remove_dir_all(&home);
+    // The home folder is the root project, because it has a wechsel folder
+    fs::create_dir_all(home.join("Music").with_extension(WECHSEL_FOLDER_EXTENSION)).unwrap();
+    fs::create_dir_all(&config_dir).unwrap();
+    let workspace = Workspace::new(&home, &config_dir);
+    let link = path_from_iter([&home, &PathBuf::from(CURRENT_PROJECT_FOLDER)]);
+    let outside = path_from_iter([
+        home_dir().expect("could not find home dir"),
+        PathBuf::from(CURRENT_PROJECT_FOLDER),
+    ]);
+    let outside_before = fs::read_link(&outside).ok();
+
+    let root = workspace.tree(false).unwrap().prj_name;
+    let path = workspace
+        .create("lib_prj", &root, &["Desktop".to_string()])
+        .unwrap();
+    assert_eq!(
+        path,
+        path_from_iter([&home, &PathBuf::from("lib_prj")]).with_extension(PROJECT_EXTENSION)
+    );
+    assert!(matches!(
+        workspace.create("other", "missing", &[]),
+        Err(WechselError::NotFound(_))
+    ));
+
+    let change = workspace
+        .change("lib_prj", ChangeOptions::default())
+        .unwrap();
+    assert_eq!(change.path, path);
+    assert_eq!(workspace.active().as_deref(), Some("lib_prj"));
+    assert_eq!(fs::read_link(&link).unwrap(), path);
+    assert!(home.join("Desktop").is_symlink());
+    assert!(matches!(
+        workspace.change("missing", ChangeOptions::default()),
+        Err(WechselError::NotFound(_))
+    ));
+
+    // Lifecycle events run the script named after them in the config dir
+    let events = home.join("events");
+    for event in [LifecycleEvent::Rename, LifecycleEvent::FolderAdd] {
+        let script = config_dir.join(event.name());
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"{} $OLD_PRJ $PRJ $FOLDER $FOLDER_PATH $(pwd)\" >> {events:?}\n",
+                event.name()
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    // The links follow the renamed active project
+    let renamed = workspace.rename("lib_prj", "renamed").unwrap();
+    assert_eq!(workspace.active().as_deref(), Some("renamed"));
+    assert_eq!(fs::read_link(&link).unwrap(), renamed.path);
+    assert!(
+        home.join("Desktop").exists(),
+        "The Desktop link is dangling"
+    );
+    assert!(workspace.tree(false).unwrap().find("renamed").is_some());
+    let project = HookProject {
+        name: "renamed".to_string(),
+        path: renamed.path.clone(),
+    };
+    workspace
+        .dispatch(LifecycleEvent::FolderAdd, &project, None, Some("Desktop"))
+        .unwrap();
+    let desktop = renamed
+        .path
+        .join("Desktop")
+        .with_extension(WECHSEL_FOLDER_EXTENSION);
+    assert_eq!(
+        fs::read_to_string(&events).unwrap(),
+        format!(
+            "on-prj-rename lib_prj renamed   {0}\non-folder-add  renamed Desktop {1} {0}\n",
+            renamed.path.display(),
+            desktop.display()
+        )
+    );
+
+    // Every event hands the project, and the old project or the folder it is about, to its script
+    let moved_from = HookProject {
+        name: "moved".to_string(),
+        path: home.join("moved").with_extension(PROJECT_EXTENSION),
+    };
+    for (event, old_project, folder) in [
+        (LifecycleEvent::Delete, None, None),
+        (LifecycleEvent::Rename, Some(&moved_from), None),
+        (LifecycleEvent::Move, Some(&moved_from), None),
+        (LifecycleEvent::Archive, None, None),
+        (LifecycleEvent::FolderAdd, None, Some("Desktop")),
+        (LifecycleEvent::FolderRemove, None, Some("Desktop")),
+    ] {
+        let output = home.join(format!("{}.env", event.name()));
+        let script = config_dir.join(event.name());
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\nprintf '%s\\n' \"${{PRJ-unset}}\" \"${{PRJ_PATH-unset}}\" \"${{OLD_PRJ-unset}}\" \"${{OLD_PRJ_PATH-unset}}\" \"${{FOLDER-unset}}\" \"${{FOLDER_PATH-unset}}\" > {output:?}\n"
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        workspace
+            .dispatch(event, &project, old_project, folder)
+            .unwrap();
+        let unset = || "unset".to_string();
+        let expected = [
+            project.name.clone(),
+            project.path.display().to_string(),
+            old_project.map_or_else(unset, |old| old.name.clone()),
+            old_project.map_or_else(unset, |old| old.path.display().to_string()),
+            folder.map_or_else(unset, str::to_string),
+            folder.map_or_else(unset, |_| desktop.display().to_string()),
+        ];
+        assert_eq!(
+            fs::read_to_string(&output)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            expected,
+            "{} got the wrong variables",
+            event.name()
+        );
+    }
+
+    assert!(matches!(
+        workspace.rename("renamed", &root),
+        Err(WechselError::Conflict(_))
+    ));
+    assert!(matches!(
+        workspace.rename("renamed", "a/b"),
+        Err(WechselError::Invalid(_))
+    ));
+
+    assert_eq!(
+        fs::read_link(&outside).ok(),
+        outside_before,
+        "The workspace changed the home folder of the environment"
+    );
+    fs::remove_dir_all(&home).unwrap();
+}
@@ -1,41 +1,25 @@
 use std::{
+    collections::BTreeMap,
     fs,
+    os::unix::fs::{MetadataExt, symlink},
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
-use dirs::home_dir;
 use rand::{Rng, distr::Alphanumeric};
+use walkdir::WalkDir;
+use wechsel::home_dir;
 use wechsel::{
-    DEFAULT_ROOT_PRJ, HOME_FOLDERS, PROJECT_EXTENSION, get_config_dir, get_home_folder_paths,
-    path_from_iter,
+    DEFAULT_ROOT_PRJ, HOME_FOLDERS, OldConfig, OldProject, PROJECT_EXTENSION, bashrc_path,
+    get_config_dir, get_home_folder_paths, get_old_config_file_path_unchecked, get_state_dir,
+    layout_version_path, log_file_path, path_from_iter,
 };
 
-// use wechsel::{
-//     DEFAULT_ROOT_PRJ,
-//     OldConfig,
-//     OldProject,
-//     PATH_TO_WECHSEL_BINARY,
-//     PROJECT_EXTENSION,
-//     assert_prj_on_change_test,
-//     call_as_user,
-//     get_old_config_file_path_unchecked,
-//     print_command_output,
-//     security_check,
-//     // test::{Project, get_current_tree},
-//     utils::{HOME_FOLDERS, get_config_dir, get_home_folder_paths, path_from_iter},
-// };
-
-// use super::{
-//     test::{change_test, init_test},
-//     utils::{generate_files, setup_home, setup_on_change_test},
-// };
-
 use crate::{Project, change_test, get_current_tree, init_test, utils::*};
 
 pub const PROJECTS_FOLDER: &str = "projects";
 
-#[test]
-fn migration_test() {
+pub(crate) fn migration_test() {
     security_check();
     let home_dir = home_dir().expect("could not find home dir");
     setup_home(&home_dir, false);
@@ -45,22 +29,114 @@ fn migration_test() {
         &get_config_dir().expect("could not find config dir"),
     );
     assert!(
-        get_current_tree().is_none(),
+        get_current_tree(false).is_none(),
         "Wechsel tree should error if an old setup is present"
     );
 
     let prjs = perform_migration(&home_dir, old_config);
+    upgrade_test(&home_dir);
     for prj in prjs.iter() {
         change_test(prj);
     }
     init_test();
     for prj in prjs.iter() {
+        allow_test(&prj.name);
         change_test(prj);
         assert_prj_on_change_test(prj);
     }
 }
 
+// `migrate --dry-run` only shows the plan and leaves every file as it is
+fn dry_run_test(home_dir: &PathBuf, old_config: &OldConfig) {
+    println!("-- migrate --dry-run --");
+    let log_file = log_file_path(&get_state_dir().expect("could not find state dir"));
+    let state = |home_dir: &PathBuf| -> BTreeMap<PathBuf, (u64, SystemTime, u64, Option<PathBuf>)> {
+        WalkDir::new(home_dir)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.path() != log_file)
+            .map(|entry| {
+                let meta = entry.metadata().expect("Could not get metadata");
+                let state = (
+                    meta.ino(),
+                    meta.modified().unwrap(),
+                    meta.len(),
+                    fs::read_link(entry.path()).ok(),
+                );
+                (entry.path().to_path_buf(), state)
+            })
+            .collect()
+    };
+
+    let before = state(home_dir);
+    let output = call_as_user(
+        &[PATH_TO_WECHSEL_BINARY, "--json", "migrate", "--dry-run"],
+        home_dir,
+    );
+    assert!(output.status.success(), "migrate --dry-run failed");
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(
+        output["result"]["steps"]
+            .as_array()
+            .is_some_and(|steps| !steps.is_empty()),
+        "migrate --dry-run didn't return a plan: {output}"
+    );
+    assert_eq!(output["result"]["active"], old_config.active.as_str());
+    let after = state(home_dir);
+
+    let changed: Vec<&PathBuf> = before
+        .iter()
+        .filter(|(path, state)| after.get(*path) != Some(state))
+        .map(|(path, _)| path)
+        .chain(after.keys().filter(|path| !before.contains_key(*path)))
+        .collect();
+    assert!(changed.is_empty(), "migrate --dry-run changed {changed:?}");
+}
+
+// A setup from before the layout version file with the misspelled shell snippet
+fn upgrade_test(home_dir: &PathBuf) {
+    println!("-- upgrade --");
+    let config_dir = get_config_dir().expect("could not find config dir");
+    let bashrc = bashrc_path();
+    let snippet = "source ~/.config/wechsel/enviroment_variables.sh\n\
+        init=$PRJ_PATH/.init-prj\n\
+        if [ -f $init ]; then\n    . $init\nfi\n";
+    fs::write(&bashrc, snippet).unwrap();
+
+    let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "upgrade", "-y"], home_dir);
+    print_command_output(output);
+
+    assert!(
+        !fs::read_to_string(&bashrc)
+            .unwrap()
+            .contains("enviroment_variables"),
+        "upgrade didn't fix the shell snippet"
+    );
+    assert!(
+        fs::read_to_string(&bashrc)
+            .unwrap()
+            .contains("\"$PRJ_INIT_SHA256\" ] && . \"$init_copy\"\n"),
+        "upgrade didn't stop the shell snippet from sourcing unchecked init scripts"
+    );
+    assert!(
+        fs::read_to_string(layout_version_path(&config_dir))
+            .ok()
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .is_some(),
+        "upgrade didn't write the layout version"
+    );
+
+    let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "upgrade", "--json"], home_dir);
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(
+        output["result"] == serde_json::json!([]),
+        "there should be nothing left to upgrade: {output}"
+    );
+    fs::write(&bashrc, "").unwrap();
+}
+
 pub(crate) fn perform_migration(home_dir: &PathBuf, old_config: OldConfig) -> Vec<Project> {
+    dry_run_test(home_dir, &old_config);
     let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "migrate", "-y"], home_dir);
 
     print_command_output(output);
@@ -143,6 +219,16 @@ pub(crate) fn setup_migration(home_dir: &PathBuf, config_dir: &Path) -> OldConfi
 
     generate_prj_files(&conf.all_prjs, None);
 
+    // The old setup linked the folders of the active project into the home folder
+    for folder in conf.all_prjs.folder.iter() {
+        let folder = path_from_iter([&PathBuf::from(&conf.all_prjs.path), &PathBuf::from(folder)]);
+        symlink(
+            &folder,
+            path_from_iter([home_dir, &PathBuf::from(folder.file_name().unwrap())]),
+        )
+        .unwrap();
+    }
+
     fs::create_dir(config_dir).unwrap();
     serde_json::to_string(&conf)
         .ok()
@@ -0,0 +1,204 @@
+use std::{collections::BTreeMap, env, fs};
+
+use wechsel::{get_state_dir, home_dir, time_log_path};
+
+use crate::{get_current_tree, utils::*};
+
+// 2024-03-04 10:00 UTC, a Monday
+const START: i64 = 1709546400;
+const HOUR: i64 = 3600;
+
+pub(crate) fn report_test() {
+    let home_dir = home_dir().expect("could not find home dir");
+    let log = time_log_path(&get_state_dir().expect("could not find state dir"));
+    let previous_log = fs::read_to_string(&log).ok();
+    // The periods of the report are in local time
+    let previous_tz = env::var_os("TZ");
+    env::set_var("TZ", "UTC");
+
+    let wechsel = |args: &[&str]| {
+        let output = call_as_user(&[&[PATH_TO_WECHSEL_BINARY], args].concat(), &home_dir);
+        if !output.status.success() {
+            print_command_output(output.clone());
+        }
+        output
+    };
+    // The root project, a child and a grandchild for the rollup
+    let root = get_current_tree(true).unwrap().tree.prj_name;
+    let [a, b, c] = [root.as_str(), "report-b", "report-c"];
+    assert!(wechsel(&["new", b, "--parent", a]).status.success());
+    assert!(wechsel(&["new", c, "--parent", b]).status.success());
+
+    let switch = |time: i64, project: &str| {
+        format!(r#"{{"time":{time},"event":"switch","project":"{project}"}}"#)
+    };
+    let event = |time: i64, event: &str| format!(r#"{{"time":{time},"event":"{event}"}}"#);
+    let lines = [
+        switch(START, a),
+        // b is active for less than a second
+        switch(START + HOUR, b),
+        switch(START + HOUR, c),
+        event(START + HOUR + HOUR / 2, "pause"),
+        event(START + HOUR + HOUR * 3 / 4, "resume"),
+        switch(START + 2 * HOUR, a),
+        // 2024-03-05 09:00
+        switch(START + 23 * HOUR, b),
+    ];
+    fs::write(&log, lines.join("\n") + "\n").unwrap();
+
+    let report = |args: &[&str]| {
+        let output = wechsel(&[&["--json", "report"], args].concat());
+        assert!(output.status.success(), "report {args:?} failed");
+        let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        output["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|row| {
+                let key = match row["period"].as_str() {
+                    Some(period) => format!("{period} {}", row["project"].as_str().unwrap()),
+                    None => row["project"].as_str().unwrap().to_string(),
+                };
+                (key, row["seconds"].as_i64().unwrap())
+            })
+            .collect::<BTreeMap<_, _>>()
+    };
+    let expect = |rows: &[(String, i64)]| rows.iter().cloned().collect::<BTreeMap<_, _>>();
+
+    // An open pause from 10:00 and a past one from 10:15 to 10:30
+    assert!(
+        wechsel(&["pause", "--from", "2024-03-05 10:00"])
+            .status
+            .success()
+    );
+    assert!(
+        wechsel(&[
+            "pause",
+            "--from",
+            "2024-03-05 10:15",
+            "--to",
+            "2024-03-05 10:30"
+        ])
+        .status
+        .success()
+    );
+    let code = wechsel(&[
+        "pause",
+        "--from",
+        "2024-03-05 11:00",
+        "--to",
+        "2024-03-05 10:00",
+    ])
+    .status
+    .code();
+    assert_eq!(
+        code,
+        Some(1),
+        "A pause that ends before it starts was accepted"
+    );
+
+    let until = ["--until", "2024-03-05 12:00"];
+    assert_eq!(
+        report(&until),
+        expect(&[
+            (a.to_string(), 22 * HOUR),
+            (b.to_string(), HOUR + HOUR * 3 / 2),
+            (c.to_string(), HOUR * 3 / 4),
+        ]),
+        "report by project"
+    );
+    assert_eq!(
+        report(&[&until[..], &["--by", "day"]].concat()),
+        expect(&[
+            (format!("2024-03-04 {a}"), 13 * HOUR),
+            (format!("2024-03-04 {b}"), 0),
+            (format!("2024-03-04 {c}"), HOUR * 3 / 4),
+            (format!("2024-03-05 {a}"), 9 * HOUR),
+            (format!("2024-03-05 {b}"), HOUR + HOUR * 3 / 2),
+        ]),
+        "report by day"
+    );
+    assert_eq!(
+        report(&[&until[..], &["--by", "week"]].concat()),
+        expect(&[
+            (format!("2024-W10 {a}"), 22 * HOUR),
+            (format!("2024-W10 {b}"), HOUR + HOUR * 3 / 2),
+            (format!("2024-W10 {c}"), HOUR * 3 / 4),
+        ]),
+        "report by week"
+    );
+    assert_eq!(
+        report(&[&until[..], &["--since", "2024-03-05"]].concat()),
+        expect(&[
+            (a.to_string(), 9 * HOUR),
+            (b.to_string(), HOUR + HOUR * 3 / 2)
+        ]),
+        "report since"
+    );
+    assert_eq!(
+        report(&["--since", "2024-03-04 10:30", "--until", "2024-03-04 11:30"]),
+        expect(&[
+            (a.to_string(), HOUR / 2),
+            (b.to_string(), 0),
+            (c.to_string(), HOUR / 2)
+        ]),
+        "report of a part of a day"
+    );
+    assert_eq!(
+        report(&[&until[..], &["--rollup", "0"]].concat()),
+        expect(&[(a.to_string(), 22 * HOUR + HOUR * 5 / 2 + HOUR * 3 / 4)]),
+        "report rolled up to the root project"
+    );
+    assert_eq!(
+        report(&[&until[..], &["--rollup", "1"]].concat()),
+        expect(&[
+            (a.to_string(), 22 * HOUR),
+            (b.to_string(), HOUR * 5 / 2 + HOUR * 3 / 4)
+        ]),
+        "report rolled up to the children of the root project"
+    );
+
+    let output = wechsel(&[&["report", "--format", "csv"], &until[..]].concat());
+    let mut expected = [
+        format!("{a},{}", 22 * HOUR),
+        format!("{b},{}", HOUR * 5 / 2),
+        format!("{c},{}", HOUR * 3 / 4),
+    ];
+    expected.sort();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("project,seconds\n{}\n", expected.join("\n")),
+        "csv report"
+    );
+    let output = wechsel(&[&["report", "--format", "json"], &until[..]].concat());
+    let rows: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(rows.as_array().map(Vec::len), Some(3), "json report");
+    let output = wechsel(&[&["report", "--by", "day"], &until[..]].concat());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        text.lines().any(|line| line.starts_with("2024-03-04")
+            && line.contains(b)
+            && line.ends_with("0h 00m")),
+        "text report misses the project that was active for less than a second:\n{text}"
+    );
+
+    assert!(wechsel(&["resume"]).status.success());
+    let content = fs::read_to_string(&log).unwrap();
+    assert!(
+        content
+            .lines()
+            .last()
+            .unwrap()
+            .contains(r#""event":"resume""#),
+        "resume wasn't logged"
+    );
+
+    match previous_log {
+        Some(previous_log) => fs::write(&log, previous_log).unwrap(),
+        None => fs::remove_file(&log).unwrap(),
+    }
+    match previous_tz {
+        Some(tz) => env::set_var("TZ", tz),
+        None => env::remove_var("TZ"),
+    }
+}
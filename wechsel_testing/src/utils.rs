@@ -4,17 +4,18 @@ use std::{
     fs::{self, Metadata},
     os::unix::fs::{MetadataExt, PermissionsExt},
     path::PathBuf,
-    process::{Command, Output},
+    process::{Child, Command, Output},
 };
 
 use rand::{Rng, distr::Alphanumeric, random};
 use walkdir::WalkDir;
 use wechsel::{
-    CURRENT_PROJECT_FOLDER, HOME_FOLDERS, ProjectTreeNode, get_config_dir, get_home_folder_paths,
-    path_from_iter,
+    CURRENT_PROJECT_FOLDER, HOME_ENV, HOME_FOLDERS, ProjectTreeNode, get_config_dir,
+    get_home_folder_paths, home_dir, path_from_iter,
 };
 
 use super::Project;
+use crate::migration::PROJECTS_FOLDER;
 
 pub const PATH_TO_WECHSEL_BINARY: &str = "wechsel";
 pub const PROJECT_ON_CHANGE_FILE_NAME: &str = ".on-prj-change";
@@ -22,6 +23,16 @@ pub const ON_CHANGE_OUTPUT_FILE: &str = "/tmp/test";
 pub const ON_CHANGE_TEST_SCRIPT: &str = "echo $PRJ > ";
 pub struct File(PathBuf, Metadata);
 
+// Background processes of a test, stopped even if the test panics
+pub struct KillOnDrop(pub Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
 impl Debug for File {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
@@ -145,8 +156,8 @@ pub fn setup_home(home: &PathBuf, create_folders: bool) {
     call_as_user(
         ["rm", "-r", CURRENT_PROJECT_FOLDER, "test_prj"]
             .iter()
-            .chain(folders.iter()),
-        // .chain([PROJECTS_FOLDER].iter()),TODO only needed for migrations
+            .chain(folders.iter())
+            .chain([PROJECTS_FOLDER].iter()),
         home,
     );
 
@@ -176,6 +187,16 @@ pub(crate) fn setup_on_change_test(path: &PathBuf) {
 
     fs::set_permissions(&on_prj_change, permissions).expect("Could not set permissions");
 }
+// Project scripts only run once they are allowed
+pub(crate) fn allow_test(prj_name: &str) {
+    let home_dir = home_dir().expect("could not find home dir");
+    let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "allow", prj_name], &home_dir);
+    assert!(
+        output.status.success(),
+        "allow failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
 pub(crate) fn assert_prj_on_change_test(prj: &Project) {
     let test_name = fs::read(ON_CHANGE_OUTPUT_FILE)
         .ok()
@@ -221,6 +242,10 @@ pub fn query_folder(home: &PathBuf) -> BTreeSet<File> {
 }
 
 pub fn security_check(/*home: &PathBuf*/) {
+    // A separate home folder keeps the files of the user safe
+    if std::env::var_os(HOME_ENV).is_some() {
+        return;
+    }
     let output = Command::new("id")
         .arg("-u")
         .output()
@@ -0,0 +1,122 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+use wechsel::home_dir;
+
+use crate::{get_current_tree, utils::*};
+
+pub(crate) fn watch_test() {
+    let home_dir = home_dir().expect("could not find home dir");
+    let tree = get_current_tree(true).unwrap();
+    let (active, root_path) = (tree.active, tree.tree.path);
+
+    let mut watch = Command::new(PATH_TO_WECHSEL_BINARY)
+        .args(["--json", "watch"])
+        .current_dir(&home_dir)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let (sender, events) = mpsc::channel();
+    let stdout = watch.stdout.take().unwrap();
+    let _watch = KillOnDrop(watch);
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let event: serde_json::Value = serde_json::from_str(&line)
+                .unwrap_or_else(|e| panic!("watch printed {line:?}, which is no json: {e}"));
+            if sender.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    // Give the watcher time to set up its watches
+    thread::sleep(Duration::from_millis(500));
+
+    println!("-- watch: debounce --");
+    // A project with folders created at once is a single event
+    let created = root_path.join("watch-a.p");
+    fs::create_dir(&created).unwrap();
+    fs::create_dir(created.join("Desktop.w")).unwrap();
+    fs::create_dir(created.join("Music.w")).unwrap();
+    let seen = wait_for(&events, "project_created", "watch-a");
+    assert_eq!(seen[0]["path"], created.to_str().unwrap());
+    let seen = [seen, quiet(&events)].concat();
+    assert_eq!(
+        seen.len(),
+        1,
+        "The project creation wasn't debounced: {seen:?}"
+    );
+
+    println!("-- watch: rename --");
+    let renamed = root_path.join("watch-b.p");
+    fs::rename(&created, &renamed).unwrap();
+    let seen = wait_for(&events, "project_renamed", "watch-b");
+    let event = &seen[seen.len() - 1];
+    assert_eq!(event["old_name"], "watch-a");
+    assert_eq!(event["old_path"], created.to_str().unwrap());
+    assert_eq!(event["path"], renamed.to_str().unwrap());
+    assert!(
+        !seen
+            .iter()
+            .any(|event| event["event"] == "project_removed" || event["event"] == "project_created"),
+        "The rename was reported as a removal and a creation: {seen:?}"
+    );
+
+    println!("-- watch: folders --");
+    fs::create_dir(renamed.join("Videos.w")).unwrap();
+    let seen = wait_for(&events, "folder_added", "watch-b");
+    assert_eq!(seen[seen.len() - 1]["folder"], "Videos");
+
+    println!("-- watch: active --");
+    let output = call_as_user(&[PATH_TO_WECHSEL_BINARY, "change", "watch-b"], &home_dir);
+    assert!(output.status.success());
+    let seen = wait_for(&events, "active_changed", "watch-b");
+    assert_eq!(seen[seen.len() - 1]["old_name"], active.as_str());
+    assert_eq!(seen[seen.len() - 1]["path"], renamed.to_str().unwrap());
+    let output = call_as_user(
+        &[PATH_TO_WECHSEL_BINARY, "change", active.as_str()],
+        &home_dir,
+    );
+    assert!(output.status.success());
+    let seen = wait_for(&events, "active_changed", &active);
+    assert_eq!(seen[seen.len() - 1]["old_path"], renamed.to_str().unwrap());
+
+    println!("-- watch: remove --");
+    fs::remove_dir_all(&renamed).unwrap();
+    wait_for(&events, "project_removed", "watch-b");
+}
+
+// Every event up to and including the first one of the kind for the project
+fn wait_for(
+    events: &Receiver<serde_json::Value>,
+    kind: &str,
+    name: &str,
+) -> Vec<serde_json::Value> {
+    let start = Instant::now();
+    let mut seen = vec![];
+    while let Some(timeout) = Duration::from_secs(5).checked_sub(start.elapsed()) {
+        let Ok(event) = events.recv_timeout(timeout) else {
+            break;
+        };
+        let found = event["event"] == kind && event["name"] == name;
+        seen.push(event);
+        if found {
+            return seen;
+        }
+    }
+    panic!("watch didn't report {kind} for {name}, only {seen:?}");
+}
+
+// The events that still arrive in the next second
+fn quiet(events: &Receiver<serde_json::Value>) -> Vec<serde_json::Value> {
+    let mut seen = vec![];
+    while let Ok(event) = events.recv_timeout(Duration::from_secs(1)) {
+        seen.push(event);
+    }
+    seen
+}
@@ -0,0 +1,15 @@
+use std::{env, path::Path};
+
+// The scenarios share one home folder and the environment of the process, so they run as a single test
+#[test]
+fn scenarios() {
+    // `cargo test --workspace` builds the wechsel binary next to the deps folder of this test
+    let exe = env::current_exe().unwrap();
+    assert!(
+        exe.parent()
+            .and_then(Path::parent)
+            .is_some_and(|dir| dir.join("wechsel").is_file()),
+        "The wechsel binary is missing, run the scenarios with `cargo test --workspace`"
+    );
+    wechsel_testing::run_scenarios();
+}